@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS register_cota_kv_pairs;
+DROP TABLE IF EXISTS check_infos;
\ No newline at end of file
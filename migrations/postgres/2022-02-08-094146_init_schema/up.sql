@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS check_infos (
+    id bigserial NOT NULL,
+    check_type smallint NOT NULL,
+    block_number bigint NOT NULL,
+    block_hash char(64) NOT NULL,
+    created_at timestamp(6) NOT NULL,
+    updated_at timestamp(6) NOT NULL,
+    PRIMARY KEY (id)
+);
+CREATE INDEX IF NOT EXISTS index_check_infos_on_block_number ON check_infos (block_number);
+
+CREATE TABLE IF NOT EXISTS register_cota_kv_pairs (
+    id bigserial NOT NULL,
+    block_number bigint NOT NULL,
+    lock_hash char(64) NOT NULL,
+    created_at timestamp(6) NOT NULL,
+    updated_at timestamp(6) NOT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT uc_register_on_lock_hash UNIQUE (lock_hash)
+);
+CREATE INDEX IF NOT EXISTS index_register_on_block_number ON register_cota_kv_pairs (block_number);
@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS check_infos (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    check_type SMALLINT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash CHAR(64) NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE INDEX IF NOT EXISTS index_check_infos_on_block_number ON check_infos (block_number);
+
+CREATE TABLE IF NOT EXISTS register_cota_kv_pairs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    block_number BIGINT NOT NULL,
+    lock_hash CHAR(64) NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    CONSTRAINT uc_register_on_lock_hash UNIQUE (lock_hash)
+);
+CREATE INDEX IF NOT EXISTS index_register_on_block_number ON register_cota_kv_pairs (block_number);
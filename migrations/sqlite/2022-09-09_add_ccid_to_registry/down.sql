@@ -0,0 +1,2 @@
+ALTER TABLE register_cota_kv_pairs
+    DROP COLUMN cota_cell_id
@@ -0,0 +1,3 @@
+-- SQLite has no unsigned bigint, the default -1 has the same bits as u64::MAX in MySQL
+ALTER TABLE register_cota_kv_pairs
+    ADD COLUMN cota_cell_id BIGINT NOT NULL DEFAULT -1
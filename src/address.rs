@@ -0,0 +1,170 @@
+use crate::error::Error;
+use crate::utils::is_mainnet;
+use bech32::{FromBase32, Variant};
+use ckb_types::bytes::Bytes;
+use ckb_types::core::ScriptHashType;
+use ckb_types::packed::Script;
+use ckb_types::prelude::*;
+
+// https://github.com/nervosnetwork/rfcs/blob/master/rfcs/0021-ckb-address-format/0021-ckb-address-format.md
+const FULL_FORMAT: u8 = 0x00;
+const SHORT_FORMAT: u8 = 0x01;
+const FULL_DATA_FORMAT: u8 = 0x02;
+const FULL_TYPE_FORMAT: u8 = 0x04;
+
+const SECP256K1_BLAKE160_CODE_HASH: &str =
+    "9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8";
+const SECP256K1_MULTISIG_CODE_HASH: &str =
+    "5c5069eb0857efc65e1bca0c07df34c31663b3622fd3876c876320fc9634e2a8";
+const MAINNET_ACP_CODE_HASH: &str =
+    "d369597ff47f29fbc0d47d2e3775370d1250b85140c670e4718af712983a2354";
+const TESTNET_ACP_CODE_HASH: &str =
+    "3419a1c09eb2567f6552ee7a8ecffd64155cffe0f1796e6e61ec088d740c1356";
+
+/// Parse the lock script from a CKB address of the full or the deprecated short and full formats,
+/// the address prefix must match the network of `IS_MAINNET`.
+pub fn parse_address(address: &str) -> Result<Script, Error> {
+    let address_error =
+        |msg: String| Error::RequestParamAddressInvalid(format!("Address '{}' {}", address, msg));
+    let (hrp, data, variant) =
+        bech32::decode(address).map_err(|e| address_error(format!("decode error: {}", e)))?;
+    let expected_hrp = if is_mainnet() { "ckb" } else { "ckt" };
+    if hrp != expected_hrp {
+        return Err(address_error(format!(
+            "prefix must be {} on the current network",
+            expected_hrp
+        )));
+    }
+    let payload =
+        Vec::<u8>::from_base32(&data).map_err(|e| address_error(format!("decode error: {}", e)))?;
+    let script = match (payload.first(), variant) {
+        (Some(&SHORT_FORMAT), Variant::Bech32) if payload.len() >= 22 => {
+            let args = &payload[2..];
+            let code_hash = match payload[1] {
+                0x00 if args.len() == 20 => SECP256K1_BLAKE160_CODE_HASH,
+                0x01 if args.len() == 20 => SECP256K1_MULTISIG_CODE_HASH,
+                0x02 if is_mainnet() => MAINNET_ACP_CODE_HASH,
+                0x02 => TESTNET_ACP_CODE_HASH,
+                _ => return Err(address_error("has unknown code hash index".to_owned())),
+            };
+            build_script(&hex::decode(code_hash).unwrap(), ScriptHashType::Type, args)
+        }
+        (Some(&FULL_DATA_FORMAT), Variant::Bech32) if payload.len() >= 33 => {
+            build_script(&payload[1..33], ScriptHashType::Data, &payload[33..])
+        }
+        (Some(&FULL_TYPE_FORMAT), Variant::Bech32) if payload.len() >= 33 => {
+            build_script(&payload[1..33], ScriptHashType::Type, &payload[33..])
+        }
+        (Some(&FULL_FORMAT), Variant::Bech32m) if payload.len() >= 34 => {
+            let hash_type = match payload[33] {
+                0x00 => ScriptHashType::Data,
+                0x01 => ScriptHashType::Type,
+                0x02 => ScriptHashType::Data1,
+                _ => return Err(address_error("has unknown hash type".to_owned())),
+            };
+            build_script(&payload[1..33], hash_type, &payload[34..])
+        }
+        _ => return Err(address_error("has unknown format".to_owned())),
+    };
+    Ok(script)
+}
+
+pub fn calc_lock_hash(lock_script: &Script) -> [u8; 32] {
+    let mut lock_hash = [0u8; 32];
+    lock_hash.copy_from_slice(lock_script.calc_script_hash().as_slice());
+    lock_hash
+}
+
+fn build_script(code_hash: &[u8], hash_type: ScriptHashType, args: &[u8]) -> Script {
+    let mut code_hash_bytes = [0u8; 32];
+    code_hash_bytes.copy_from_slice(code_hash);
+    Script::new_builder()
+        .code_hash(code_hash_bytes.pack())
+        .hash_type(hash_type.into())
+        .args(Bytes::from(args.to_vec()).pack())
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bech32::ToBase32;
+
+    const ARGS: &str = "b39bbc0b3673c7d36450bc14cfcdad2d559c6c64";
+
+    fn encode_address(hrp: &str, payload: Vec<u8>, variant: Variant) -> String {
+        bech32::encode(hrp, payload.to_base32(), variant).unwrap()
+    }
+
+    #[test]
+    fn test_parse_address() {
+        let code_hash = hex::decode(SECP256K1_BLAKE160_CODE_HASH).unwrap();
+        let args = hex::decode(ARGS).unwrap();
+        let lock_hash = calc_lock_hash(&build_script(&code_hash, ScriptHashType::Type, &args));
+
+        let short_payload = [vec![SHORT_FORMAT, 0x00], args.clone()].concat();
+        let full_type_payload = [vec![FULL_TYPE_FORMAT], code_hash.clone(), args.clone()].concat();
+        let full_payload = [
+            vec![FULL_FORMAT],
+            code_hash.clone(),
+            vec![0x01],
+            args.clone(),
+        ]
+        .concat();
+        let addresses = vec![
+            encode_address("ckt", short_payload, Variant::Bech32),
+            encode_address("ckt", full_type_payload, Variant::Bech32),
+            encode_address("ckt", full_payload.clone(), Variant::Bech32m),
+        ];
+        for address in addresses.iter() {
+            assert_eq!(calc_lock_hash(&parse_address(address).unwrap()), lock_hash);
+        }
+
+        let data_lock_hash =
+            calc_lock_hash(&build_script(&code_hash, ScriptHashType::Data1, &args));
+        let mut data_payload = full_payload;
+        data_payload[33] = 0x02;
+        let address = encode_address("ckt", data_payload, Variant::Bech32m);
+        assert_eq!(
+            calc_lock_hash(&parse_address(&address).unwrap()),
+            data_lock_hash
+        );
+    }
+
+    #[test]
+    fn test_parse_address_error() {
+        let args = hex::decode(ARGS).unwrap();
+        let payload = [vec![SHORT_FORMAT, 0x00], args.clone()].concat();
+
+        let address = encode_address("ckb", payload.clone(), Variant::Bech32);
+        assert_eq!(
+            parse_address(&address).err(),
+            Some(Error::RequestParamAddressInvalid(format!(
+                "Address '{}' prefix must be ckt on the current network",
+                address
+            )))
+        );
+
+        let address = encode_address("ckt", payload, Variant::Bech32m);
+        assert_eq!(
+            parse_address(&address).err(),
+            Some(Error::RequestParamAddressInvalid(format!(
+                "Address '{}' has unknown format",
+                address
+            )))
+        );
+
+        let address = encode_address(
+            "ckt",
+            [vec![SHORT_FORMAT, 0x03], args].concat(),
+            Variant::Bech32,
+        );
+        assert_eq!(
+            parse_address(&address).err(),
+            Some(Error::RequestParamAddressInvalid(format!(
+                "Address '{}' has unknown code hash index",
+                address
+            )))
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use crate::audit::{audit_registry, get_latest_audit};
+use crate::blocking::{run_blocking, BlockingPool};
+use crate::config::{get_config, reload_config};
+use crate::db::{get_registered_count, get_syncer_tip_block_number};
+use crate::indexer::cache::get_cached_registry_cell;
+use crate::smt::entry::{drop_pending_leaves, resync_registry_smt};
+use crate::smt::stats::get_smt_stats;
+use crate::ROCKS_DB;
+use jsonrpc_http_server::hyper::{header, Body, Request, Response, StatusCode};
+use jsonrpc_http_server::jsonrpc_core::{Error, IoHandler, Params, Value};
+use jsonrpc_http_server::{RequestMiddlewareAction, Server, ServerBuilder};
+use log::{info, warn};
+use serde_json::json;
+use std::env;
+use tokio::runtime::Handle;
+
+pub fn build_admin_rpc_handler() -> IoHandler {
+    let mut io = IoHandler::default();
+    io.add_method("admin_resync_smt", resync_smt_rpc);
+    io.add_method("admin_drop_pending_leaves", drop_pending_leaves_rpc);
+    io.add_method("admin_compact_rocksdb", compact_rocksdb_rpc);
+    io.add_method("admin_reload_config", reload_config_rpc);
+    io.add_method("admin_dump_state", dump_state_rpc);
+    io.add_method("admin_run_audit", run_audit_rpc);
+    io
+}
+
+/// Start the admin RPC server on `ADMIN_ADDRESS` if `ADMIN_TOKEN` is set, and every request must
+/// carry the header `Authorization: Bearer <ADMIN_TOKEN>`.
+pub fn start_admin_server(handle: &Handle) -> Option<Server> {
+    let token = match env::var("ADMIN_TOKEN") {
+        Ok(token) if !token.is_empty() => token,
+        _ => {
+            info!("Admin RPC server is disabled without ADMIN_TOKEN");
+            return None;
+        }
+    };
+    let address = env::var("ADMIN_ADDRESS").unwrap_or_else(|_| "127.0.0.1:3051".to_owned());
+    let server = ServerBuilder::new(build_admin_rpc_handler())
+        .event_loop_executor(handle.clone())
+        .threads(1)
+        .request_middleware(move |request: Request<Body>| {
+            let authorization = request
+                .headers()
+                .get(header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok());
+            if is_authorized(authorization, &token) {
+                request.into()
+            } else {
+                warn!("Unauthorized admin request");
+                RequestMiddlewareAction::from(
+                    Response::builder()
+                        .status(StatusCode::UNAUTHORIZED)
+                        .body(Body::from("Unauthorized"))
+                        .expect("Unauthorized response build error"),
+                )
+            }
+        })
+        .start_http(&address.parse().expect("ADMIN_ADDRESS is invalid"))
+        .expect("Admin RPC server start error");
+    info!("Admin RPC server starts on {}", address);
+    Some(server)
+}
+
+/// Compare the bearer token in constant time
+fn is_authorized(authorization: Option<&str>, token: &str) -> bool {
+    let request_token = match authorization.and_then(|value| value.strip_prefix("Bearer ")) {
+        Some(request_token) => request_token.as_bytes(),
+        None => return false,
+    };
+    request_token.len() == token.len()
+        && request_token
+            .iter()
+            .zip(token.as_bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+pub async fn resync_smt_rpc(_params: Params) -> Result<Value, Error> {
+    info!("Admin resync smt request");
+    let report = run_blocking(BlockingPool::Register, resync_registry_smt)
+        .await
+        .map_err(|err| err.into())?;
+    Ok(report.to_json(false))
+}
+
+pub async fn drop_pending_leaves_rpc(_params: Params) -> Result<Value, Error> {
+    info!("Admin drop pending leaves request");
+    let (old_root, new_root, dropped) = run_blocking(BlockingPool::Register, drop_pending_leaves)
+        .await
+        .map_err(|err| err.into())?;
+    Ok(json!({
+        "old_smt_root_hash": hex::encode(old_root.as_slice()),
+        "new_smt_root_hash": hex::encode(new_root.as_slice()),
+        "dropped_leaves_count": dropped,
+    }))
+}
+
+pub async fn compact_rocksdb_rpc(_params: Params) -> Result<Value, Error> {
+    info!("Admin compact rocksdb request");
+    run_blocking(BlockingPool::Register, || ROCKS_DB.compact())
+        .await
+        .map_err(|err| err.into())?;
+    Ok(Value::Bool(true))
+}
+
+pub async fn reload_config_rpc(_params: Params) -> Result<Value, Error> {
+    info!("Admin reload config request");
+    let reloaded = reload_config().map_err(|err| err.into())?;
+    Ok(json!({ "reloaded": reloaded }))
+}
+
+pub async fn dump_state_rpc(_params: Params) -> Result<Value, Error> {
+    info!("Admin dump state request");
+    let smt_stats = run_blocking(BlockingPool::Query, get_smt_stats)
+        .await
+        .map_err(|err| err.into())?;
+    let (block_number, registered_count) = run_blocking(BlockingPool::Query, || {
+        Ok((get_syncer_tip_block_number()?, get_registered_count()?))
+    })
+    .await
+    .map_err(|err| err.into())?;
+    let config = get_config();
+    let registry_cell = match get_cached_registry_cell().await {
+        Ok(cell) => json!({
+            "smt_root_hash": hex::encode(cell.info.smt_root),
+            "block_number": cell.block_number,
+        }),
+        Err(err) => {
+            warn!("Dump state registry cell error: {}", err.to_msg());
+            Value::Null
+        }
+    };
+    Ok(json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "smt": {
+            "smt_root_hash": hex::encode(smt_stats.root.as_slice()),
+            "leaves_count": smt_stats.leaves_count,
+            "temp_leaves_count": smt_stats.temp_leaves_count,
+        },
+        "registry_cell": registry_cell,
+        "syncer_tip_block_number": block_number,
+        "registered_count": registered_count,
+        "latest_audit": get_latest_audit().map_or(Value::Null, |report| report.to_json()),
+        "config": {
+            "MAX_BATCH_SIZE": config.max_batch_size,
+            "REGISTRY_CELL_TTL": config.registry_cell_ttl.as_secs(),
+        },
+    }))
+}
+
+pub async fn run_audit_rpc(_params: Params) -> Result<Value, Error> {
+    info!("Admin run audit request");
+    let report = audit_registry().await.map_err(|err| err.into())?;
+    Ok(report.to_json())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_authorized() {
+        assert!(is_authorized(Some("Bearer secret"), "secret"));
+        assert!(!is_authorized(Some("Bearer secre"), "secret"));
+        assert!(!is_authorized(Some("Bearer secreT"), "secret"));
+        assert!(!is_authorized(Some("secret"), "secret"));
+        assert!(!is_authorized(None, "secret"));
+    }
+}
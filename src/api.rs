@@ -1,18 +1,61 @@
-use crate::db::{check_lock_hashes_registered, get_syncer_tip_block_number};
+use crate::api_key::{ApiKeyMiddleware, ApiKeys, RequestMeta};
+use crate::audit::get_latest_audit;
+use crate::blocking::{run_blocking, BlockingPool};
+use crate::db::{
+    check_lock_hashes_registered, get_registered_count, get_registered_lock_hashes,
+    get_registrations_per_block_range, get_registrations_per_day, get_syncer_tip_block_number,
+};
+use crate::error::Error as AggregatorError;
+use crate::indexer::cache::get_cached_registry_cell;
+use crate::indexer::index::RegistryCell;
+use crate::smt::diff::get_leaf_states;
 use crate::smt::entry::generate_registry_smt;
-use crate::utils::parse_request_param;
-use jsonrpc_http_server::jsonrpc_core::serde_json::{Map, Number};
-use jsonrpc_http_server::jsonrpc_core::{Error, Params, Value};
-use log::info;
+use crate::smt::history::{generate_proof_at, get_root_at};
+use crate::smt::stats::get_smt_stats;
+use crate::smt::verify::{decode_registry_entry, verify_registry_entry};
+use crate::utils::{parse_lock_hash_param, parse_registry_param};
+use jsonrpc_http_server::jsonrpc_core::serde_json::{to_value, Map, Number};
+use jsonrpc_http_server::jsonrpc_core::{Error, MetaIoHandler, Params, Value};
+use log::{info, warn};
+use serde::Deserialize;
+
+const DEFAULT_PAGE_LIMIT: i64 = 100;
+const MAX_PAGE_LIMIT: i64 = 1000;
+const DEFAULT_BLOCK_RANGE_SIZE: u64 = 10000;
+
+pub type RpcHandler = MetaIoHandler<RequestMeta, ApiKeyMiddleware>;
+
+/// The API keys are checked for every method if `API_KEYS` is set
+pub fn build_rpc_handler() -> RpcHandler {
+    build_rpc_handler_with_api_keys(ApiKeys::from_env())
+}
+
+pub fn build_rpc_handler_with_api_keys(api_keys: Option<ApiKeys>) -> RpcHandler {
+    let mut io = MetaIoHandler::with_middleware(ApiKeyMiddleware::new(api_keys));
+    io.add_method("register_cota_cells", register_rpc);
+    io.add_method("check_registered_lock_hashes", check_registered_rpc);
+    io.add_method("get_registry_cell", get_registry_cell_rpc);
+    io.add_method("decode_registry_entry", decode_registry_entry_rpc);
+    io.add_method("verify_registry_entry", verify_registry_entry_rpc);
+    io.add_method("get_registered_lock_hashes", get_registered_lock_hashes_rpc);
+    io.add_method("get_registry_stats", get_registry_stats_rpc);
+    io.add_method("get_registry_root_at", get_registry_root_at_rpc);
+    io.add_method("get_registry_proof_at", get_registry_proof_at_rpc);
+    io.add_method("get_registry_audit", get_registry_audit_rpc);
+    io.add_method("get_registry_leaf", get_registry_leaf_rpc);
+    io
+}
 
 pub async fn register_rpc(params: Params) -> Result<Value, Error> {
     info!("Register cota cells request: {:?}", params);
     let registries: Vec<Value> = Params::parse(params)?;
-    let lock_hashes = parse_request_param::<32>(registries).map_err(|err| err.into())?;
-    let (root_hash, registry_entry) = generate_registry_smt(lock_hashes)
+    let registries = parse_registry_param(registries).map_err(|err| err.into())?;
+    let (root_hash, registry_entry) = generate_registry_smt(registries)
+        .await
+        .map_err(|err| err.into())?;
+    let block_number = run_blocking(BlockingPool::Query, get_syncer_tip_block_number)
         .await
         .map_err(|err| err.into())?;
-    let block_number = get_syncer_tip_block_number().map_err(|err| err.into())?;
     let mut response = Map::new();
     response.insert("smt_root_hash".to_string(), Value::String(root_hash));
     response.insert(
@@ -29,9 +72,12 @@ pub async fn register_rpc(params: Params) -> Result<Value, Error> {
 pub async fn check_registered_rpc(params: Params) -> Result<Value, Error> {
     info!("Check registered request: {:?}", params);
     let registries: Vec<Value> = Params::parse(params)?;
-    let lock_hashes = parse_request_param::<32>(registries).map_err(|err| err.into())?;
-    let (registry_state, block_height) =
-        check_lock_hashes_registered(lock_hashes).map_err(|err| err.into())?;
+    let lock_hashes = parse_lock_hash_param(registries).map_err(|err| err.into())?;
+    let (registry_state, block_height) = run_blocking(BlockingPool::Query, move || {
+        check_lock_hashes_registered(lock_hashes)
+    })
+    .await
+    .map_err(|err| err.into())?;
     let mut response = Map::new();
     response.insert("registered".to_string(), Value::Bool(registry_state));
     response.insert(
@@ -40,3 +86,877 @@ pub async fn check_registered_rpc(params: Params) -> Result<Value, Error> {
     );
     Ok(Value::Object(response))
 }
+
+pub async fn get_registry_cell_rpc(_params: Params) -> Result<Value, Error> {
+    info!("Get registry cell request");
+    let RegistryCell {
+        info: registry_info,
+        out_point,
+        block_number,
+    } = get_cached_registry_cell().await.map_err(|err| err.into())?;
+    let mut response = Map::new();
+    response.insert(
+        "version".to_string(),
+        Value::Number(Number::from(registry_info.version)),
+    );
+    response.insert(
+        "smt_root_hash".to_string(),
+        Value::String(hex::encode(registry_info.smt_root)),
+    );
+    response.insert(
+        "extension".to_string(),
+        registry_info.extension.map_or(Value::Null, |extension| {
+            Value::String(hex::encode(extension))
+        }),
+    );
+    response.insert(
+        "out_point".to_string(),
+        to_value(out_point).map_err(|_e| Error::internal_error())?,
+    );
+    response.insert(
+        "block_number".to_string(),
+        Value::Number(Number::from(block_number)),
+    );
+    Ok(Value::Object(response))
+}
+
+pub async fn decode_registry_entry_rpc(params: Params) -> Result<Value, Error> {
+    info!("Decode registry entry request: {:?}", params);
+    let params: Vec<String> = Params::parse(params)?;
+    if params.is_empty() || params.len() > 2 {
+        return Err(Error::invalid_params(
+            "Expected the registry entry and an optional SMT root",
+        ));
+    }
+    decode_registry_entry(&params[0], params.get(1).map(|root| root.as_str()))
+        .map_err(|err| err.into())
+}
+
+pub async fn verify_registry_entry_rpc(params: Params) -> Result<Value, Error> {
+    info!("Verify registry entry request: {:?}", params);
+    let (entry, old_root, new_root): (String, String, String) = Params::parse(params)?;
+    verify_registry_entry(&entry, &old_root, &new_root).map_err(|err| err.into())
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RegisteredLockHashesParams {
+    cursor:            Option<i64>,
+    limit:             Option<i64>,
+    from_block_number: Option<u64>,
+    to_block_number:   Option<u64>,
+}
+
+pub async fn get_registered_lock_hashes_rpc(params: Params) -> Result<Value, Error> {
+    info!("Get registered lock hashes request: {:?}", params);
+    let RegisteredLockHashesParams {
+        cursor,
+        limit,
+        from_block_number,
+        to_block_number,
+    } = match params {
+        Params::None => RegisteredLockHashesParams::default(),
+        params => params.parse()?,
+    };
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    if limit <= 0 || limit > MAX_PAGE_LIMIT {
+        let err = AggregatorError::RequestParamsInvalid(format!(
+            "Request limit must be between 1 and {}",
+            MAX_PAGE_LIMIT
+        ));
+        return Err(err.into());
+    }
+    let registries = run_blocking(BlockingPool::Query, move || {
+        get_registered_lock_hashes(cursor, limit, from_block_number, to_block_number)
+    })
+    .await
+    .map_err(|err| err.into())?;
+    // The last page is shorter than the limit and has no cursor for the next page
+    let last_cursor = if registries.len() as i64 == limit {
+        registries.last().map_or(Value::Null, |registry| {
+            Value::Number(Number::from(registry.id))
+        })
+    } else {
+        Value::Null
+    };
+    let registries = registries
+        .into_iter()
+        .map(|registry| {
+            let mut registry_json = Map::new();
+            registry_json.insert(
+                "lock_hash".to_string(),
+                Value::String(hex::encode(registry.lock_hash)),
+            );
+            registry_json.insert(
+                "cota_cell_id".to_string(),
+                if registry.ccid == u64::MAX {
+                    Value::Null
+                } else {
+                    Value::Number(Number::from(registry.ccid))
+                },
+            );
+            registry_json.insert(
+                "block_number".to_string(),
+                Value::Number(Number::from(registry.block_number)),
+            );
+            Value::Object(registry_json)
+        })
+        .collect();
+    let mut response = Map::new();
+    response.insert("registries".to_string(), Value::Array(registries));
+    response.insert("last_cursor".to_string(), last_cursor);
+    Ok(Value::Object(response))
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RegistryStatsParams {
+    block_range_size: Option<u64>,
+}
+
+pub async fn get_registry_stats_rpc(params: Params) -> Result<Value, Error> {
+    info!("Get registry stats request: {:?}", params);
+    let RegistryStatsParams { block_range_size } = match params {
+        Params::None => RegistryStatsParams::default(),
+        params => params.parse()?,
+    };
+    let block_range_size = block_range_size.unwrap_or(DEFAULT_BLOCK_RANGE_SIZE);
+    if block_range_size == 0 {
+        let err = AggregatorError::RequestParamsInvalid(
+            "Request block_range_size must be positive".to_owned(),
+        );
+        return Err(err.into());
+    }
+    let (total_registered, block_ranges, days, tip_block_number) =
+        run_blocking(BlockingPool::Query, move || {
+            Ok((
+                get_registered_count()?,
+                get_registrations_per_block_range(block_range_size)?,
+                get_registrations_per_day()?,
+                get_syncer_tip_block_number()?,
+            ))
+        })
+        .await
+        .map_err(|err| err.into())?;
+    let smt_stats = run_blocking(BlockingPool::Query, get_smt_stats)
+        .await
+        .map_err(|err| err.into())?;
+    // The stats are still useful for the dashboards when the CKB indexer is unavailable
+    let onchain_root = match get_cached_registry_cell().await {
+        Ok(cell) => Value::String(hex::encode(cell.info.smt_root)),
+        Err(err) => {
+            warn!("Get registry cell for stats error: {}", err.to_msg());
+            Value::Null
+        }
+    };
+
+    let block_ranges = block_ranges
+        .into_iter()
+        .map(|(start, count)| {
+            let mut range = Map::new();
+            range.insert(
+                "from_block_number".to_string(),
+                Value::Number(Number::from(start)),
+            );
+            range.insert(
+                "to_block_number".to_string(),
+                Value::Number(Number::from(start + block_range_size - 1)),
+            );
+            range.insert("count".to_string(), Value::Number(Number::from(count)));
+            Value::Object(range)
+        })
+        .collect();
+    let days = days
+        .into_iter()
+        .map(|(day, count)| {
+            let mut daily = Map::new();
+            daily.insert("day".to_string(), Value::String(day));
+            daily.insert("count".to_string(), Value::Number(Number::from(count)));
+            Value::Object(daily)
+        })
+        .collect();
+    let mut response = Map::new();
+    response.insert(
+        "total_registered".to_string(),
+        Value::Number(Number::from(total_registered)),
+    );
+    response.insert(
+        "registrations_per_block_range".to_string(),
+        Value::Array(block_ranges),
+    );
+    response.insert("registrations_per_day".to_string(), Value::Array(days));
+    response.insert(
+        "smt_leaves_count".to_string(),
+        Value::Number(Number::from(smt_stats.leaves_count)),
+    );
+    response.insert(
+        "pending_temp_leaves_count".to_string(),
+        Value::Number(Number::from(smt_stats.temp_leaves_count)),
+    );
+    response.insert(
+        "local_smt_root_hash".to_string(),
+        Value::String(hex::encode(smt_stats.root.as_slice())),
+    );
+    response.insert("onchain_smt_root_hash".to_string(), onchain_root);
+    response.insert(
+        "block_number".to_string(),
+        Value::Number(Number::from(tip_block_number)),
+    );
+    Ok(Value::Object(response))
+}
+
+pub async fn get_registry_root_at_rpc(params: Params) -> Result<Value, Error> {
+    info!("Get registry root at request: {:?}", params);
+    let (block_number,): (u64,) = Params::parse(params)?;
+    let (root_hash, registered_count) =
+        run_blocking(BlockingPool::History, move || get_root_at(block_number))
+            .await
+            .map_err(|err| err.into())?;
+    let mut response = Map::new();
+    response.insert(
+        "smt_root_hash".to_string(),
+        Value::String(hex::encode(root_hash.as_slice())),
+    );
+    response.insert(
+        "registered_count".to_string(),
+        Value::Number(Number::from(registered_count)),
+    );
+    response.insert(
+        "block_number".to_string(),
+        Value::Number(Number::from(block_number)),
+    );
+    response.insert(
+        "cota_cell_id_as_of".to_string(),
+        Value::String("latest".to_string()),
+    );
+    Ok(Value::Object(response))
+}
+
+pub async fn get_registry_proof_at_rpc(params: Params) -> Result<Value, Error> {
+    info!("Get registry proof at request: {:?}", params);
+    let (block_number, registries): (u64, Vec<Value>) = Params::parse(params)?;
+    let lock_hashes = parse_lock_hash_param(registries).map_err(|err| err.into())?;
+    let (root_hash, leaves, proof) = run_blocking(BlockingPool::History, move || {
+        generate_proof_at(block_number, lock_hashes)
+    })
+    .await
+    .map_err(|err| err.into())?;
+    let leaves = leaves
+        .into_iter()
+        .map(|(key, value)| {
+            let mut leaf = Map::new();
+            leaf.insert(
+                "lock_hash".to_string(),
+                Value::String(hex::encode(key.as_slice())),
+            );
+            leaf.insert(
+                "state".to_string(),
+                Value::String(hex::encode(value.as_slice())),
+            );
+            leaf.insert("registered".to_string(), Value::Bool(!value.is_zero()));
+            Value::Object(leaf)
+        })
+        .collect();
+    let mut response = Map::new();
+    response.insert(
+        "smt_root_hash".to_string(),
+        Value::String(hex::encode(root_hash.as_slice())),
+    );
+    response.insert("leaves".to_string(), Value::Array(leaves));
+    response.insert("proof".to_string(), Value::String(hex::encode(proof)));
+    response.insert(
+        "block_number".to_string(),
+        Value::Number(Number::from(block_number)),
+    );
+    response.insert(
+        "cota_cell_id_as_of".to_string(),
+        Value::String("latest".to_string()),
+    );
+    Ok(Value::Object(response))
+}
+
+/// Return the latest audit report, and the audit on demand is only run by `admin_run_audit`
+pub async fn get_registry_audit_rpc(params: Params) -> Result<Value, Error> {
+    info!("Get registry audit request: {:?}", params);
+    let refresh = match params {
+        Params::None => false,
+        params => params
+            .parse::<Vec<bool>>()?
+            .first()
+            .copied()
+            .unwrap_or(false),
+    };
+    if refresh {
+        let err = AggregatorError::RequestParamsInvalid(
+            "The audit on demand is run by the admin RPC admin_run_audit".to_string(),
+        );
+        return Err(err.into());
+    }
+    Ok(get_latest_audit().map_or(Value::Null, |report| report.to_json()))
+}
+
+pub async fn get_registry_leaf_rpc(params: Params) -> Result<Value, Error> {
+    info!("Get registry leaf request: {:?}", params);
+    let registries: Vec<Value> = Params::parse(params)?;
+    let lock_hashes = parse_lock_hash_param(registries).map_err(|err| err.into())?;
+    let leaves = run_blocking(BlockingPool::Query, move || get_leaf_states(lock_hashes))
+        .await
+        .map_err(|err| err.into())?;
+    let mut response = Map::new();
+    response.insert(
+        "leaves".to_string(),
+        Value::Array(leaves.iter().map(|leaf| leaf.to_json()).collect()),
+    );
+    Ok(Value::Object(response))
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::build_rpc_handler_with_api_keys;
+    use crate::api_key::{ApiKeyQuota, ApiKeys, RequestMeta};
+    use crate::error::API_KEY_ERROR_CODE;
+    use crate::smt::verify::RegistryEntry;
+    use crate::test_support::harness::{
+        generate_registry_entry, registered_leaf, Harness, REGISTERED_LOCK_HASH,
+        SYNCER_TIP_BLOCK_NUMBER,
+    };
+    use cota_smt::molecule::prelude::*;
+    use cota_smt::registry::CotaNFTRegistryEntries;
+    use cota_smt::smt::{Blake2bHasher, H256};
+    use serde_json::{json, Value};
+    use sparse_merkle_tree::CompiledMerkleProof;
+
+    fn lock_hash_param(lock_hash: [u8; 32]) -> String {
+        format!("0x{}", hex::encode(lock_hash))
+    }
+
+    #[test]
+    fn test_register_cota_cells() {
+        let lock_hash = [2u8; 32];
+        let update_leaves = vec![(H256::from(lock_hash), H256::from([255u8; 32]))];
+        let (root, registry_entry) =
+            generate_registry_entry(vec![registered_leaf()], update_leaves.clone());
+
+        let response =
+            Harness::get().request("register_cota_cells", json!([lock_hash_param(lock_hash)]));
+        assert_eq!(
+            response,
+            json!({
+                "id": 1,
+                "jsonrpc": "2.0",
+                "result": {
+                    "block_number": SYNCER_TIP_BLOCK_NUMBER,
+                    "registry_smt_entry": registry_entry,
+                    "smt_root_hash": hex::encode(root.as_slice()),
+                },
+            })
+        );
+
+        let entry_bytes =
+            hex::decode(response["result"]["registry_smt_entry"].as_str().unwrap()).unwrap();
+        let registry_entries = CotaNFTRegistryEntries::from_slice(&entry_bytes).unwrap();
+        let registries = registry_entries.registries();
+        assert_eq!(registries.len(), 1);
+        assert_eq!(
+            registries.get(0).unwrap().lock_hash().as_slice(),
+            &lock_hash
+        );
+        let proof = CompiledMerkleProof(registry_entries.proof().raw_data().to_vec());
+        assert!(proof.verify::<Blake2bHasher>(&root, update_leaves).unwrap());
+    }
+
+    #[test]
+    fn test_register_cota_cells_with_cota_cell_id() {
+        let lock_hash = [10u8; 32];
+        let mut state = [255u8; 32];
+        state[0..8].copy_from_slice(&5u64.to_be_bytes());
+        let update_leaves = vec![(H256::from(lock_hash), H256::from(state))];
+        let (root, registry_entry) =
+            generate_registry_entry(vec![registered_leaf()], update_leaves);
+
+        let response = Harness::get().request(
+            "register_cota_cells",
+            json!([{ "lock_hash": lock_hash_param(lock_hash), "cota_cell_id": 5 }]),
+        );
+        assert_eq!(
+            response["result"]["registry_smt_entry"],
+            json!(registry_entry)
+        );
+        assert_eq!(
+            response["result"]["smt_root_hash"],
+            json!(hex::encode(root.as_slice()))
+        );
+    }
+
+    #[test]
+    fn test_register_after_reset_temp_leaves() {
+        let (onchain_root, _) = generate_registry_entry(vec![registered_leaf()], vec![]);
+        let update_leaves = vec![(H256::from([12u8; 32]), H256::from([255u8; 32]))];
+        let (root, registry_entry) =
+            generate_registry_entry(vec![registered_leaf()], update_leaves);
+
+        Harness::get().with_onchain_root(onchain_root, |harness| {
+            let response =
+                harness.request("register_cota_cells", json!([lock_hash_param([11u8; 32])]));
+            assert!(response["result"].is_object());
+            // The pending leaf of the last registration is reverted to match the registry cell
+            let response =
+                harness.request("register_cota_cells", json!([lock_hash_param([12u8; 32])]));
+            assert_eq!(
+                response["result"]["smt_root_hash"],
+                json!(hex::encode(root.as_slice()))
+            );
+            assert_eq!(
+                response["result"]["registry_smt_entry"],
+                json!(registry_entry)
+            );
+        });
+    }
+
+    #[test]
+    fn test_register_after_rebuild_from_database() {
+        let update_leaves = vec![(H256::from([13u8; 32]), H256::from([255u8; 32]))];
+        let (root, registry_entry) =
+            generate_registry_entry(vec![registered_leaf()], update_leaves);
+
+        // No reset matches the unknown root of the registry cell, so the SMT is rebuilt from
+        // the database
+        Harness::get().with_onchain_root(H256::from([7u8; 32]), |harness| {
+            let response =
+                harness.request("register_cota_cells", json!([lock_hash_param([13u8; 32])]));
+            assert_eq!(
+                response["result"]["smt_root_hash"],
+                json!(hex::encode(root.as_slice()))
+            );
+            assert_eq!(
+                response["result"]["registry_smt_entry"],
+                json!(registry_entry)
+            );
+        });
+    }
+
+    #[test]
+    fn test_register_registered_lock_hash() {
+        let response = Harness::get().request(
+            "register_cota_cells",
+            json!([lock_hash_param(REGISTERED_LOCK_HASH)]),
+        );
+        assert_eq!(
+            response,
+            json!({
+                "id": 1,
+                "jsonrpc": "2.0",
+                "error": {
+                    "code": -32602,
+                    "message": "The lock_hash has registered",
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_registered_lock_hashes() {
+        let response = Harness::get().request(
+            "check_registered_lock_hashes",
+            json!([lock_hash_param(REGISTERED_LOCK_HASH)]),
+        );
+        assert_eq!(
+            response,
+            json!({
+                "id": 1,
+                "jsonrpc": "2.0",
+                "result": {
+                    "block_number": SYNCER_TIP_BLOCK_NUMBER,
+                    "registered": true,
+                },
+            })
+        );
+
+        let response = Harness::get().request(
+            "check_registered_lock_hashes",
+            json!([
+                lock_hash_param(REGISTERED_LOCK_HASH),
+                lock_hash_param([3u8; 32])
+            ]),
+        );
+        assert_eq!(
+            response,
+            json!({
+                "id": 1,
+                "jsonrpc": "2.0",
+                "result": {
+                    "block_number": SYNCER_TIP_BLOCK_NUMBER,
+                    "registered": false,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_registry_entry() {
+        let leaf = (H256::from([4u8; 32]), H256::from([255u8; 32]));
+        let (root, registry_entry) = generate_registry_entry(vec![], vec![leaf]);
+        let proof = hex::encode(RegistryEntry::from_hex(&registry_entry).unwrap().proof);
+
+        let response = Harness::get().request(
+            "decode_registry_entry",
+            json!([registry_entry, hex::encode(root.as_slice())]),
+        );
+        assert_eq!(
+            response,
+            json!({
+                "id": 1,
+                "jsonrpc": "2.0",
+                "result": {
+                    "registries": [{
+                        "lock_hash": hex::encode([4u8; 32]),
+                        "state": hex::encode([255u8; 32]),
+                    }],
+                    "proof": proof,
+                    "verified": true,
+                },
+            })
+        );
+
+        let response = Harness::get().request("decode_registry_entry", json!([]));
+        assert_eq!(response["error"]["code"], json!(-32602));
+    }
+
+    #[test]
+    fn test_get_registered_lock_hashes() {
+        let response = Harness::get().request("get_registered_lock_hashes", json!({}));
+        assert_eq!(
+            response,
+            json!({
+                "id": 1,
+                "jsonrpc": "2.0",
+                "result": {
+                    "registries": [{
+                        "lock_hash": hex::encode(REGISTERED_LOCK_HASH),
+                        "cota_cell_id": null,
+                        "block_number": SYNCER_TIP_BLOCK_NUMBER,
+                    }],
+                    "last_cursor": null,
+                },
+            })
+        );
+
+        let response = Harness::get().request(
+            "get_registered_lock_hashes",
+            json!({ "limit": 1, "to_block_number": SYNCER_TIP_BLOCK_NUMBER }),
+        );
+        let last_cursor = response["result"]["last_cursor"].clone();
+        assert_eq!(
+            response["result"]["registries"].as_array().unwrap().len(),
+            1
+        );
+        assert!(last_cursor.is_number());
+
+        let response = Harness::get().request(
+            "get_registered_lock_hashes",
+            json!({ "cursor": last_cursor, "limit": 1 }),
+        );
+        assert_eq!(response["result"]["registries"], json!([]));
+
+        let response = Harness::get().request(
+            "get_registered_lock_hashes",
+            json!({ "from_block_number": SYNCER_TIP_BLOCK_NUMBER + 1 }),
+        );
+        assert_eq!(response["result"]["registries"], json!([]));
+
+        let response = Harness::get().request("get_registered_lock_hashes", json!({ "limit": 0 }));
+        assert_eq!(response["error"]["code"], json!(-32602));
+    }
+
+    #[test]
+    fn test_get_registry_stats() {
+        let (onchain_root, _) = generate_registry_entry(vec![registered_leaf()], vec![]);
+        let response =
+            Harness::get().request("get_registry_stats", json!({ "block_range_size": 1000 }));
+        let result = &response["result"];
+        assert_eq!(result["total_registered"], json!(1));
+        assert_eq!(
+            result["registrations_per_block_range"],
+            json!([{ "from_block_number": 0, "to_block_number": 999, "count": 1 }])
+        );
+        assert_eq!(
+            result["registrations_per_day"].as_array().unwrap()[0]["count"],
+            json!(1)
+        );
+        assert_eq!(
+            result["onchain_smt_root_hash"],
+            json!(hex::encode(onchain_root.as_slice()))
+        );
+        assert_eq!(result["block_number"], json!(SYNCER_TIP_BLOCK_NUMBER));
+        // The local SMT depends on whether the other tests have registered before
+        assert!(result["local_smt_root_hash"].is_string());
+        assert!(result["smt_leaves_count"].is_u64());
+        assert!(result["pending_temp_leaves_count"].is_u64());
+    }
+
+    #[test]
+    fn test_get_registry_root_at() {
+        let (root, _) = generate_registry_entry(vec![registered_leaf()], vec![]);
+        let response =
+            Harness::get().request("get_registry_root_at", json!([SYNCER_TIP_BLOCK_NUMBER]));
+        assert_eq!(
+            response["result"],
+            json!({
+                "smt_root_hash": hex::encode(root.as_slice()),
+                "registered_count": 1,
+                "block_number": SYNCER_TIP_BLOCK_NUMBER,
+                "cota_cell_id_as_of": "latest",
+            })
+        );
+        // The root of the same block number comes from the cache
+        let cached_response =
+            Harness::get().request("get_registry_root_at", json!([SYNCER_TIP_BLOCK_NUMBER]));
+        assert_eq!(cached_response, response);
+
+        let response =
+            Harness::get().request("get_registry_root_at", json!([SYNCER_TIP_BLOCK_NUMBER - 1]));
+        assert_eq!(
+            response["result"]["smt_root_hash"],
+            json!(hex::encode([0u8; 32]))
+        );
+
+        let response =
+            Harness::get().request("get_registry_root_at", json!([SYNCER_TIP_BLOCK_NUMBER + 1]));
+        assert_eq!(
+            response["error"]["message"],
+            json!("Request params error: Block number 101 is beyond the syncer tip 100")
+        );
+    }
+
+    #[test]
+    fn test_get_registry_proof_at() {
+        let (root, _) = generate_registry_entry(vec![registered_leaf()], vec![]);
+        let absent_lock_hash = [5u8; 32];
+        let response = Harness::get().request(
+            "get_registry_proof_at",
+            json!([SYNCER_TIP_BLOCK_NUMBER, [
+                lock_hash_param(REGISTERED_LOCK_HASH),
+                lock_hash_param(absent_lock_hash)
+            ]]),
+        );
+        let result = &response["result"];
+        assert_eq!(result["smt_root_hash"], json!(hex::encode(root.as_slice())));
+        assert_eq!(result["cota_cell_id_as_of"], json!("latest"));
+        assert_eq!(
+            result["leaves"],
+            json!([
+                {
+                    "lock_hash": hex::encode(REGISTERED_LOCK_HASH),
+                    "state": hex::encode([255u8; 32]),
+                    "registered": true,
+                },
+                {
+                    "lock_hash": hex::encode(absent_lock_hash),
+                    "state": hex::encode([0u8; 32]),
+                    "registered": false,
+                },
+            ])
+        );
+        let proof = CompiledMerkleProof(hex::decode(result["proof"].as_str().unwrap()).unwrap());
+        let leaves = vec![
+            registered_leaf(),
+            (H256::from(absent_lock_hash), H256::zero()),
+        ];
+        assert!(proof.verify::<Blake2bHasher>(&root, leaves).unwrap());
+    }
+
+    #[test]
+    fn test_get_registry_audit() {
+        let (root, _) = generate_registry_entry(vec![registered_leaf()], vec![]);
+        // The registration builds the RocksDB SMT from MySQL and leaves a pending leaf
+        let response =
+            Harness::get().request("register_cota_cells", json!([lock_hash_param([6u8; 32])]));
+        assert!(response["result"].is_object());
+
+        let response = Harness::get().request("get_registry_audit", json!([true]));
+        assert_eq!(
+            response["error"]["message"],
+            json!(
+                "Request params error: The audit on demand is run by the admin RPC admin_run_audit"
+            )
+        );
+
+        let response = Harness::get().admin_request("admin_run_audit", json!([]));
+        let result = &response["result"];
+        assert_eq!(result["consistent"], json!(true));
+        assert_eq!(result["divergences"], json!(0));
+        assert_eq!(
+            result["mysql_smt_root_hash"],
+            json!(hex::encode(root.as_slice()))
+        );
+        assert_eq!(
+            result["onchain_smt_root_hash"],
+            json!(hex::encode(root.as_slice()))
+        );
+        assert_eq!(
+            result["diff"],
+            json!({
+                "additions_count": 0,
+                "removals_count": 0,
+                "changes_count": 0,
+                "additions": [],
+                "removals": [],
+                "changes": [],
+            })
+        );
+
+        let response = Harness::get().request("get_registry_audit", json!([]));
+        assert_eq!(
+            response["result"]["mysql_smt_root_hash"],
+            json!(hex::encode(root.as_slice()))
+        );
+    }
+
+    #[test]
+    fn test_get_registry_leaf() {
+        // The registration builds the RocksDB SMT from MySQL
+        let response =
+            Harness::get().request("register_cota_cells", json!([lock_hash_param([8u8; 32])]));
+        assert!(response["result"].is_object());
+
+        let response = Harness::get().request(
+            "get_registry_leaf",
+            json!([
+                lock_hash_param(REGISTERED_LOCK_HASH),
+                lock_hash_param([9u8; 32])
+            ]),
+        );
+        assert_eq!(
+            response["result"]["leaves"],
+            json!([
+                {
+                    "lock_hash": hex::encode(REGISTERED_LOCK_HASH),
+                    "registered": true,
+                    "cota_cell_id": null,
+                    "expected_state": hex::encode([255u8; 32]),
+                    "local_state": hex::encode([255u8; 32]),
+                    "pending": false,
+                    "consistent": true,
+                },
+                {
+                    "lock_hash": hex::encode([9u8; 32]),
+                    "registered": false,
+                    "cota_cell_id": null,
+                    "expected_state": null,
+                    "local_state": null,
+                    "pending": false,
+                    "consistent": true,
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_api_keys() {
+        // The handler with the API keys shares the database and the indexer of the harness
+        Harness::get();
+        let default_quota = ApiKeyQuota {
+            registrations_per_hour: 1,
+            max_lock_hashes:        Some(2),
+        };
+        let io =
+            build_rpc_handler_with_api_keys(Some(ApiKeys::parse("key", default_quota).unwrap()));
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let request = |method: &str, params: Value, api_key: Option<&str>| -> Value {
+            let request = json!({ "id": 1, "jsonrpc": "2.0", "method": method, "params": params });
+            let meta = RequestMeta {
+                api_key: api_key.map(|api_key| api_key.to_owned()),
+            };
+            let response = runtime
+                .block_on(io.handle_request(&request.to_string(), meta))
+                .unwrap();
+            serde_json::from_str(&response).unwrap()
+        };
+        let registered = json!([lock_hash_param(REGISTERED_LOCK_HASH)]);
+        let invalid_key_error = json!({
+            "code": API_KEY_ERROR_CODE,
+            "message": "API key is missing or invalid",
+        });
+
+        let response = request("check_registered_lock_hashes", registered.clone(), None);
+        assert_eq!(response["error"], invalid_key_error);
+        let response = request(
+            "check_registered_lock_hashes",
+            registered.clone(),
+            Some("other"),
+        );
+        assert_eq!(response["error"], invalid_key_error);
+        let response = request(
+            "check_registered_lock_hashes",
+            registered.clone(),
+            Some("key"),
+        );
+        assert_eq!(response["result"]["registered"], json!(true));
+
+        let lock_hashes: Vec<String> = (20u8..23).map(|i| lock_hash_param([i; 32])).collect();
+        let response = request("register_cota_cells", json!(lock_hashes), Some("key"));
+        assert_eq!(
+            response["error"],
+            json!({
+                "code": API_KEY_ERROR_CODE,
+                "message": "API key quota exceeded: 3 lock hashes exceed the max 2 per call",
+            })
+        );
+
+        // The registrations by notifications are dropped without touching the SMT
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": "register_cota_cells",
+            "params": [lock_hash_param([23u8; 32])],
+        });
+        let meta = RequestMeta {
+            api_key: Some("key".to_owned()),
+        };
+        assert_eq!(
+            runtime.block_on(io.handle_request(&notification.to_string(), meta)),
+            None
+        );
+        let response =
+            Harness::get().request("get_registry_leaf", json!([lock_hash_param([23u8; 32])]));
+        assert_eq!(response["result"]["leaves"][0]["local_state"], Value::Null);
+
+        // The failed registrations are not counted in the quota of 1 per hour
+        for _ in 0..2 {
+            let response = request("register_cota_cells", registered.clone(), Some("key"));
+            assert_eq!(
+                response["error"]["message"],
+                json!("The lock_hash has registered")
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_lock_hash() {
+        let lock_hash = lock_hash_param(REGISTERED_LOCK_HASH);
+        let response = Harness::get().request(
+            "check_registered_lock_hashes",
+            json!([lock_hash, "0x1234", lock_hash]),
+        );
+        assert_eq!(
+            response,
+            json!({
+                "id": 1,
+                "jsonrpc": "2.0",
+                "error": {
+                    "code": -32602,
+                    "message": "2 request params are invalid",
+                    "data": [
+                        {
+                            "index": 1,
+                            "message": "Request lock_hash '\"0x1234\"' must be 32bytes hex string starting with 0x",
+                        },
+                        {
+                            "index": 2,
+                            "message": "Request param is duplicate of the param 0",
+                        },
+                    ],
+                },
+            })
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use crate::config::get_config;
+use crate::error::Error;
+use crate::utils::{parse_env, parse_env_opt};
+use jsonrpc_http_server::hyper::{Body, Request};
+use jsonrpc_http_server::jsonrpc_core::futures::future::{ready, Either, FutureExt};
+use jsonrpc_http_server::jsonrpc_core::{
+    Call, FutureOutput, FutureResponse, Metadata, MethodCall, Middleware, Output, Params,
+};
+use log::warn;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::env;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const API_KEY_HEADER: &str = "x-api-key";
+const QUOTA_WINDOW: Duration = Duration::from_secs(3600);
+
+#[derive(Clone, Debug, Default)]
+pub struct RequestMeta {
+    pub api_key: Option<String>,
+}
+
+impl Metadata for RequestMeta {}
+
+impl RequestMeta {
+    pub fn from_request(request: &Request<Body>) -> Self {
+        let api_key = request
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_owned());
+        RequestMeta { api_key }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ApiKeyQuota {
+    pub registrations_per_hour: u64,
+    // None to follow `MAX_BATCH_SIZE`
+    pub max_lock_hashes:        Option<usize>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ApiKeys(HashMap<String, ApiKeyQuota>);
+
+impl ApiKeys {
+    /// The API keys are disabled if `API_KEYS` is not set
+    pub fn from_env() -> Option<Self> {
+        let api_keys = env::var("API_KEYS").ok()?;
+        let default_quota = ApiKeyQuota {
+            registrations_per_hour: parse_env("API_KEY_REGISTRATIONS_PER_HOUR", 1000),
+            max_lock_hashes:        parse_env_opt("API_KEY_MAX_LOCK_HASHES"),
+        };
+        Some(
+            ApiKeys::parse(&api_keys, default_quota)
+                .unwrap_or_else(|err| panic!("API_KEYS is invalid: {}", err)),
+        )
+    }
+
+    /// Parse the comma separated `key[:registrations_per_hour[:max_lock_hashes]]`, and the
+    /// quotas which are absent take the default ones.
+    pub fn parse(api_keys: &str, default_quota: ApiKeyQuota) -> Result<Self, String> {
+        let mut keys = HashMap::new();
+        for item in api_keys
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+        {
+            let parts: Vec<&str> = item.split(':').collect();
+            if parts.len() > 3 || parts[0].is_empty() {
+                return Err(format!(
+                    "'{}' must be key[:per_hour[:max_lock_hashes]]",
+                    item
+                ));
+            }
+            let mut quota = default_quota;
+            if let Some(per_hour) = parts.get(1) {
+                quota.registrations_per_hour = per_hour
+                    .parse()
+                    .map_err(|_| format!("'{}' has invalid registrations per hour", item))?;
+            }
+            if let Some(max_lock_hashes) = parts.get(2) {
+                quota.max_lock_hashes = Some(
+                    max_lock_hashes
+                        .parse()
+                        .map_err(|_| format!("'{}' has invalid max lock hashes", item))?,
+                );
+            }
+            keys.insert(parts[0].to_owned(), quota);
+        }
+        Ok(ApiKeys(keys))
+    }
+
+    pub fn get(&self, api_key: Option<&str>) -> Option<&ApiKeyQuota> {
+        api_key.and_then(|api_key| self.0.get(api_key))
+    }
+}
+
+/// The registrations of every API key in the current hour window
+#[derive(Debug, Default)]
+pub struct RegistrationCounter {
+    usages: Mutex<HashMap<String, (Instant, u64)>>,
+}
+
+impl RegistrationCounter {
+    /// Count the registrations in the current window and return the start of the window
+    pub fn acquire(
+        &self,
+        api_key: &str,
+        quota: u64,
+        count: u64,
+        now: Instant,
+    ) -> Result<Instant, Error> {
+        let mut usages = self.usages.lock();
+        let usage = usages.entry(api_key.to_owned()).or_insert((now, 0));
+        if now.duration_since(usage.0) >= QUOTA_WINDOW {
+            *usage = (now, 0);
+        }
+        if usage.1 + count > quota {
+            return Err(Error::ApiKeyQuotaExceeded(format!(
+                "{} registrations exceed the remaining {} of {} per hour",
+                count,
+                quota.saturating_sub(usage.1),
+                quota
+            )));
+        }
+        usage.1 += count;
+        Ok(usage.0)
+    }
+
+    /// Give back the registrations counted in the window, and nothing is given back if the window
+    /// has been reset since they were counted
+    pub fn release(&self, api_key: &str, window: Instant, count: u64) {
+        if let Some(usage) = self.usages.lock().get_mut(api_key) {
+            if usage.0 == window {
+                usage.1 = usage.1.saturating_sub(count);
+            }
+        }
+    }
+}
+
+/// Reject the calls without a known API key, and the registrations beyond the quotas of the key.
+/// The registrations which fail are not counted.
+#[derive(Default)]
+pub struct ApiKeyMiddleware {
+    api_keys: Option<ApiKeys>,
+    counter:  Arc<RegistrationCounter>,
+}
+
+impl ApiKeyMiddleware {
+    pub fn new(api_keys: Option<ApiKeys>) -> Self {
+        ApiKeyMiddleware {
+            api_keys,
+            counter: Arc::new(RegistrationCounter::default()),
+        }
+    }
+
+    /// Return the API key, the quota window and the number of the counted registrations
+    fn check(
+        &self,
+        api_keys: &ApiKeys,
+        method_call: &MethodCall,
+        api_key: Option<&str>,
+    ) -> Result<Option<(String, Instant, u64)>, Error> {
+        let quota = api_keys.get(api_key).ok_or(Error::ApiKeyInvalid)?;
+        let lock_hashes_count = match (&method_call.params, method_call.method.as_str()) {
+            (Params::Array(params), "register_cota_cells") => params.len(),
+            _ => return Ok(None),
+        };
+        let max_lock_hashes = quota
+            .max_lock_hashes
+            .unwrap_or_else(|| get_config().max_batch_size);
+        if lock_hashes_count > max_lock_hashes {
+            return Err(Error::ApiKeyQuotaExceeded(format!(
+                "{} lock hashes exceed the max {} per call",
+                lock_hashes_count, max_lock_hashes
+            )));
+        }
+        let api_key = api_key.unwrap_or_default().to_owned();
+        let window = self.counter.acquire(
+            &api_key,
+            quota.registrations_per_hour,
+            lock_hashes_count as u64,
+            Instant::now(),
+        )?;
+        Ok(Some((api_key, window, lock_hashes_count as u64)))
+    }
+}
+
+impl Middleware<RequestMeta> for ApiKeyMiddleware {
+    type CallFuture = FutureOutput;
+    type Future = FutureResponse;
+
+    fn on_call<F, X>(&self, call: Call, meta: RequestMeta, next: F) -> Either<Self::CallFuture, X>
+    where
+        F: Fn(Call, RequestMeta) -> X + Send + Sync,
+        X: Future<Output = Option<Output>> + Send + 'static,
+    {
+        let api_keys = match self.api_keys.as_ref() {
+            Some(api_keys) => api_keys,
+            None => return Either::Right(next(call, meta)),
+        };
+        let api_key = meta.api_key.as_deref();
+        let method_call = match &call {
+            Call::MethodCall(method_call) => method_call,
+            // The notifications have no responses, so the unauthorized ones are dropped. The
+            // registrations are dropped too, as their failures can't be told to give back the quota
+            Call::Notification(notification)
+                if api_keys.get(api_key).is_none()
+                    || notification.method == "register_cota_cells" =>
+            {
+                warn!("Drop {} notification", notification.method);
+                return Either::Left(Box::pin(ready(None)));
+            }
+            _ => return Either::Right(next(call, meta)),
+        };
+        match self.check(api_keys, method_call, api_key) {
+            Ok(None) => Either::Right(next(call, meta)),
+            Ok(Some((api_key, window, count))) => {
+                let counter = Arc::clone(&self.counter);
+                Either::Left(Box::pin(next(call, meta).map(move |output| {
+                    if let Some(Output::Failure(_)) = output {
+                        counter.release(&api_key, window, count);
+                    }
+                    output
+                })))
+            }
+            Err(err) => {
+                warn!("Reject {} request: {}", method_call.method, err);
+                let output =
+                    Output::from(Err(err.into()), method_call.id.clone(), method_call.jsonrpc);
+                Either::Left(Box::pin(ready(Some(output))))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULT_QUOTA: ApiKeyQuota = ApiKeyQuota {
+        registrations_per_hour: 1000,
+        max_lock_hashes:        None,
+    };
+
+    #[test]
+    fn test_parse_api_keys() {
+        let api_keys = ApiKeys::parse("key1, key2:10,key3:10:2,", DEFAULT_QUOTA).unwrap();
+        assert_eq!(api_keys.get(Some("key1")), Some(&DEFAULT_QUOTA));
+        assert_eq!(
+            api_keys.get(Some("key2")),
+            Some(&ApiKeyQuota {
+                registrations_per_hour: 10,
+                max_lock_hashes:        None,
+            })
+        );
+        assert_eq!(
+            api_keys.get(Some("key3")),
+            Some(&ApiKeyQuota {
+                registrations_per_hour: 10,
+                max_lock_hashes:        Some(2),
+            })
+        );
+        assert_eq!(api_keys.get(Some("key4")), None);
+        assert_eq!(api_keys.get(None), None);
+
+        assert_eq!(
+            ApiKeys::parse("key1:ten", DEFAULT_QUOTA),
+            Err("'key1:ten' has invalid registrations per hour".to_owned())
+        );
+        assert!(ApiKeys::parse(":10", DEFAULT_QUOTA).is_err());
+    }
+
+    #[test]
+    fn test_registration_counter() {
+        let counter = RegistrationCounter::default();
+        let now = Instant::now();
+        assert_eq!(counter.acquire("key", 10, 6, now), Ok(now));
+        assert_eq!(
+            counter.acquire("key", 10, 5, now),
+            Err(Error::ApiKeyQuotaExceeded(
+                "5 registrations exceed the remaining 4 of 10 per hour".to_owned()
+            ))
+        );
+        assert_eq!(counter.acquire("other", 10, 5, now), Ok(now));
+
+        counter.release("key", now, 6);
+        assert_eq!(counter.acquire("key", 10, 10, now), Ok(now));
+        assert!(counter.acquire("key", 10, 1, now).is_err());
+        assert_eq!(
+            counter.acquire("key", 10, 1, now + QUOTA_WINDOW),
+            Ok(now + QUOTA_WINDOW)
+        );
+    }
+
+    #[test]
+    fn test_release_after_window_reset() {
+        let counter = RegistrationCounter::default();
+        let now = Instant::now();
+        let window = counter.acquire("key", 10, 6, now).unwrap();
+        // The window is reset before the failed registration of the last window is released
+        let next_window = now + QUOTA_WINDOW;
+        assert_eq!(counter.acquire("key", 10, 8, next_window), Ok(next_window));
+        counter.release("key", window, 6);
+        assert!(counter.acquire("key", 10, 3, next_window).is_err());
+        assert_eq!(counter.acquire("key", 10, 2, next_window), Ok(next_window));
+    }
+}
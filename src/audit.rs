@@ -0,0 +1,145 @@
+use crate::blocking::{run_blocking, BlockingPool};
+use crate::error::Error;
+use crate::indexer::index::get_registry_cell;
+use crate::smt::diff::{LeafDiff, SMTComparison};
+use crate::smt::entry::compare_registry_smt;
+use crate::utils::{parse_env, parse_env_opt};
+use chrono::prelude::*;
+use cota_smt::smt::H256;
+use lazy_static::lazy_static;
+use log::{error, info, warn};
+use parking_lot::RwLock;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::runtime::Handle;
+
+// The leaves of every kind of differences kept in the report
+const DIFF_LEAVES_LIMIT: usize = 100;
+
+lazy_static! {
+    static ref LATEST_AUDIT: RwLock<Option<AuditReport>> = RwLock::new(None);
+}
+
+#[derive(Clone)]
+pub struct AuditReport {
+    pub checked_at:     DateTime<Utc>,
+    pub mysql_root:     H256,
+    pub local_root:     H256,
+    pub onchain_root:   Option<H256>,
+    pub pending_leaves: usize,
+    pub diff:           LeafDiff,
+    // The number of the consecutive audits which have found divergence, including this one
+    pub divergences:    u32,
+}
+
+impl AuditReport {
+    /// The RocksDB leaves must match MySQL except the pending ones, and the registry cell must be
+    /// either the SMT of MySQL or the SMT with the pending registration which is not synced yet.
+    pub fn is_consistent(&self) -> bool {
+        let onchain_consistent = self.onchain_root.map_or(true, |onchain_root| {
+            onchain_root == self.mysql_root || onchain_root == self.local_root
+        });
+        self.diff.is_empty() && onchain_consistent
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "checked_at": self.checked_at.to_rfc3339(),
+            "consistent": self.is_consistent(),
+            "divergences": self.divergences,
+            "mysql_smt_root_hash": hex::encode(self.mysql_root.as_slice()),
+            "local_smt_root_hash": hex::encode(self.local_root.as_slice()),
+            "onchain_smt_root_hash": self.onchain_root.map(|root| hex::encode(root.as_slice())),
+            "pending_leaves_count": self.pending_leaves,
+            "diff": self.diff.to_json(DIFF_LEAVES_LIMIT),
+        })
+    }
+}
+
+pub fn get_latest_audit() -> Option<AuditReport> {
+    LATEST_AUDIT.read().clone()
+}
+
+/// Recompute the SMT root from MySQL and compare it and the leaves with RocksDB and the registry
+/// cell. The on-chain root is left out of the comparison if the CKB indexer fails.
+///
+/// It scans all the registrations, so it runs in the bounded register pool and is only triggered
+/// by the auditor and the admin RPC.
+pub async fn audit_registry() -> Result<AuditReport, Error> {
+    let onchain_root = match get_registry_cell().await {
+        Ok(cell) => Some(H256::from(cell.info.smt_root)),
+        Err(err) => {
+            warn!("Audit registry cell error: {}", err.to_msg());
+            None
+        }
+    };
+    let SMTComparison {
+        mysql_root,
+        local_root,
+        pending_leaves,
+        diff,
+    } = run_blocking(BlockingPool::Register, compare_registry_smt).await?;
+    let previous_divergences = LATEST_AUDIT
+        .read()
+        .as_ref()
+        .map_or(0, |report| report.divergences);
+    let mut report = AuditReport {
+        checked_at: Utc::now(),
+        mysql_root,
+        local_root,
+        onchain_root,
+        pending_leaves,
+        diff,
+        divergences: 0,
+    };
+    if !report.is_consistent() {
+        report.divergences = previous_divergences + 1;
+    }
+    *LATEST_AUDIT.write() = Some(report.clone());
+    Ok(report)
+}
+
+/// Audit the registry every `AUDIT_INTERVAL` seconds, 0 to disable. The divergence is alerted
+/// after `AUDIT_ALERT_THRESHOLD` consecutive audits find it, since the syncer may lag behind the
+/// registry cell for a while.
+pub fn spawn_registry_auditor(handle: &Handle) {
+    let interval: u64 = parse_env("AUDIT_INTERVAL", 600);
+    if interval == 0 {
+        return;
+    }
+    let alert_threshold: u32 = parse_env("AUDIT_ALERT_THRESHOLD", 3);
+    let webhook: Option<String> = parse_env_opt("AUDIT_ALERT_WEBHOOK");
+    handle.spawn(async move {
+        let client = reqwest::Client::new();
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+        loop {
+            ticker.tick().await;
+            let report = match audit_registry().await {
+                Ok(report) => report,
+                Err(err) => {
+                    warn!("Audit registry error: {}", err.to_msg());
+                    continue;
+                }
+            };
+            if report.is_consistent() {
+                info!("Registry audit is consistent");
+                continue;
+            }
+            let report_json = report.to_json();
+            if report.divergences < alert_threshold {
+                warn!("Registry audit divergence: {}", report_json);
+                continue;
+            }
+            error!("Registry audit divergence alert: {}", report_json);
+            // Post the alert only once when the divergence reaches the threshold
+            if report.divergences > alert_threshold {
+                continue;
+            }
+            if let Some(webhook) = webhook.as_ref() {
+                if let Err(e) = client.post(webhook).json(&report_json).send().await {
+                    warn!("Registry audit alert webhook error: {}", e.to_string());
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,54 @@
+use crate::error::Error;
+use crate::utils::parse_env;
+use lazy_static::lazy_static;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::spawn_blocking;
+use tokio::time::timeout;
+
+lazy_static! {
+    static ref REGISTER_CONCURRENCY: usize = parse_env("REGISTER_CONCURRENCY", 2);
+    static ref QUERY_CONCURRENCY: usize = parse_env("QUERY_CONCURRENCY", 16);
+    static ref HISTORY_CONCURRENCY: usize = parse_env("HISTORY_CONCURRENCY", 1);
+    static ref REQUEST_TIMEOUT: Duration = Duration::from_secs(parse_env("REQUEST_TIMEOUT", 60));
+    static ref REGISTER_SEMAPHORE: Arc<Semaphore> = Arc::new(Semaphore::new(*REGISTER_CONCURRENCY));
+    static ref QUERY_SEMAPHORE: Arc<Semaphore> = Arc::new(Semaphore::new(*QUERY_CONCURRENCY));
+    static ref HISTORY_SEMAPHORE: Arc<Semaphore> = Arc::new(Semaphore::new(*HISTORY_CONCURRENCY));
+}
+
+/// The bounded pools to run blocking database and RocksDB work, registrations which may rebuild
+/// the SMT and the historical SMTs built in memory never take the threads of the cheap queries.
+#[derive(Clone, Copy, Debug)]
+pub enum BlockingPool {
+    Register,
+    Query,
+    History,
+}
+
+/// Run the blocking operator outside of the RPC executor, waiting at most `REQUEST_TIMEOUT` for
+/// a free slot of the pool.
+///
+/// An operator which has started always runs to the end and returns its result, so a request
+/// which times out has done nothing.
+pub async fn run_blocking<F, T>(pool: BlockingPool, operator: F) -> Result<T, Error>
+where
+    F: FnOnce() -> Result<T, Error> + Send + 'static,
+    T: Send + 'static,
+{
+    let semaphore = match pool {
+        BlockingPool::Register => Arc::clone(&REGISTER_SEMAPHORE),
+        BlockingPool::Query => Arc::clone(&QUERY_SEMAPHORE),
+        BlockingPool::History => Arc::clone(&HISTORY_SEMAPHORE),
+    };
+    let permit = timeout(*REQUEST_TIMEOUT, semaphore.acquire_owned())
+        .await
+        .map_err(|_| Error::RequestTimeout(REQUEST_TIMEOUT.as_secs()))?
+        .map_err(|e| Error::BlockingTaskError(e.to_string()))?;
+    spawn_blocking(move || {
+        let _permit = permit;
+        operator()
+    })
+    .await
+    .map_err(|e| Error::BlockingTaskError(e.to_string()))?
+}
@@ -0,0 +1,126 @@
+use crate::error::Error;
+use lazy_static::lazy_static;
+use log::info;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+// The config which can be reloaded from the .env file at runtime
+const RELOADABLE_CONFIG: [&str; 2] = ["MAX_BATCH_SIZE", "REGISTRY_CELL_TTL"];
+
+lazy_static! {
+    static ref CONFIG: RwLock<Config> =
+        RwLock::new(Config::from_env().unwrap_or_else(|err| panic!("{}", err.to_msg())));
+}
+
+/// The reloadable config, which is loaded once and read by the handlers without parsing the env
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Config {
+    pub max_batch_size:    usize,
+    pub registry_cell_ttl: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            max_batch_size:    100,
+            registry_cell_ttl: Duration::from_secs(6),
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, Error> {
+        Config::default().merge(|key| env::var(key).ok())
+    }
+
+    /// Override the config with the values which are present, and all of them must be valid
+    fn merge<F>(self, get: F) -> Result<Self, Error>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut config = self;
+        if let Some(value) = get("MAX_BATCH_SIZE") {
+            config.max_batch_size = parse_config("MAX_BATCH_SIZE", &value)?;
+        }
+        if let Some(value) = get("REGISTRY_CELL_TTL") {
+            config.registry_cell_ttl =
+                Duration::from_secs(parse_config("REGISTRY_CELL_TTL", &value)?);
+        }
+        Ok(config)
+    }
+}
+
+fn parse_config<T: FromStr>(key: &str, value: &str) -> Result<T, Error> {
+    value
+        .parse::<T>()
+        .map_err(|_| Error::ConfigError(format!("{} '{}' is invalid", key, value)))
+}
+
+pub fn get_config() -> Config {
+    *CONFIG.read()
+}
+
+/// Load the config from the env at startup, so that the invalid values are reported before
+/// the server starts
+pub fn init_config() -> Result<(), Error> {
+    let config = Config::from_env()?;
+    *CONFIG.write() = config;
+    Ok(())
+}
+
+#[cfg(test)]
+pub fn set_config(config: Config) {
+    *CONFIG.write() = config;
+}
+
+/// Apply the reloadable config of the .env file after all of them are validated, and return
+/// the reloaded keys. The env is left untouched and the other config takes effect after restart.
+pub fn reload_config() -> Result<Vec<String>, Error> {
+    let dotenv_error = |e: dotenv::Error| Error::ConfigError(e.to_string());
+    let mut values = HashMap::new();
+    for item in dotenv::dotenv_iter().map_err(dotenv_error)? {
+        let (key, value) = item.map_err(dotenv_error)?;
+        if RELOADABLE_CONFIG.contains(&key.as_str()) {
+            values.insert(key, value);
+        }
+    }
+    let mut config = CONFIG.write();
+    *config = config.merge(|key| values.get(key).cloned())?;
+    info!("Reload config {:?}", *config);
+    Ok(RELOADABLE_CONFIG
+        .iter()
+        .filter(|key| values.contains_key(**key))
+        .map(|key| key.to_string())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_config() {
+        let values: HashMap<&str, &str> = [("MAX_BATCH_SIZE", "20")].iter().cloned().collect();
+        let config = Config::default()
+            .merge(|key| values.get(key).map(|value| value.to_string()))
+            .unwrap();
+        assert_eq!(config, Config {
+            max_batch_size:    20,
+            registry_cell_ttl: Duration::from_secs(6),
+        });
+
+        let values: HashMap<&str, &str> = [("MAX_BATCH_SIZE", "20"), ("REGISTRY_CELL_TTL", "-1")]
+            .iter()
+            .cloned()
+            .collect();
+        assert_eq!(
+            Config::default().merge(|key| values.get(key).map(|value| value.to_string())),
+            Err(Error::ConfigError(
+                "REGISTRY_CELL_TTL '-1' is invalid".to_owned()
+            ))
+        );
+    }
+}
@@ -2,51 +2,137 @@ use super::schema::register_cota_kv_pairs::dsl::*;
 use crate::error::Error;
 use crate::schema::check_infos::dsl::block_number;
 use crate::schema::check_infos::dsl::check_infos;
-use crate::utils::parse_bytes_n;
+use crate::schema::register_cota_kv_pairs::dsl::block_number as registry_block_number;
+use crate::utils::{parse_bytes_n, parse_env, parse_env_opt};
 use crate::POOL;
 use cota_smt::smt::H256;
-use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::dsl::count_star;
+use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
+use diesel::sql_types::{BigInt, Nullable, Text};
 use diesel::*;
-use jsonrpc_http_server::jsonrpc_core::serde_json::from_str;
 use log::error;
 use serde::{Deserialize, Serialize};
 use std::env;
+use std::time::Duration;
 
-pub type SqlConnectionPool = Pool<ConnectionManager<MysqlConnection>>;
+#[cfg(not(any(feature = "mysql", feature = "postgres", feature = "sqlite")))]
+compile_error!("One of the features `mysql`, `postgres` or `sqlite` must be enabled");
 
+#[cfg(any(
+    all(feature = "mysql", feature = "postgres"),
+    all(feature = "mysql", feature = "sqlite"),
+    all(feature = "postgres", feature = "sqlite")
+))]
+compile_error!("Only one of the features `mysql`, `postgres` or `sqlite` can be enabled");
+
+#[cfg(feature = "mysql")]
+pub type DbConnection = MysqlConnection;
+#[cfg(feature = "postgres")]
+pub type DbConnection = PgConnection;
+#[cfg(feature = "sqlite")]
+pub type DbConnection = SqliteConnection;
+
+// Only MySQL supports unsigned bigint columns, PostgreSQL and SQLite store the block number and
+// cota_cell_id as signed bigint with the same bits.
+#[cfg(feature = "mysql")]
+type SqlU64 = u64;
+#[cfg(not(feature = "mysql"))]
+type SqlU64 = i64;
+
+#[cfg(feature = "mysql")]
+fn to_u64(value: SqlU64) -> u64 {
+    value
+}
+
+#[cfg(not(feature = "mysql"))]
+fn to_u64(value: SqlU64) -> u64 {
+    value as u64
+}
+
+#[cfg(feature = "mysql")]
+fn from_u64(value: u64) -> SqlU64 {
+    value
+}
+
+#[cfg(not(feature = "mysql"))]
+fn from_u64(value: u64) -> SqlU64 {
+    value as SqlU64
+}
+
+// The expressions to format the registration day as YYYY-MM-DD in every backend
+#[cfg(feature = "mysql")]
+const REGISTRATION_DAY: &str = "DATE_FORMAT(created_at, '%Y-%m-%d')";
+#[cfg(feature = "postgres")]
+const REGISTRATION_DAY: &str = "TO_CHAR(created_at, 'YYYY-MM-DD')";
+#[cfg(feature = "sqlite")]
+const REGISTRATION_DAY: &str = "STRFTIME('%Y-%m-%d', created_at)";
+
+// The SQL type to cast the latest update time to text in every backend
+#[cfg(feature = "mysql")]
+const TEXT_CAST_TYPE: &str = "CHAR";
+#[cfg(not(feature = "mysql"))]
+const TEXT_CAST_TYPE: &str = "TEXT";
+
+pub type SqlConnectionPool = Pool<ConnectionManager<DbConnection>>;
+
+pub type SqlConnection = PooledConnection<ConnectionManager<DbConnection>>;
+
+/// The pool is built without connecting, so an unavailable database fails the requests with
+/// `DatabaseQueryError` in `get_connection` until it recovers.
 pub fn init_connection_pool() -> SqlConnectionPool {
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let manager = ConnectionManager::<MysqlConnection>::new(database_url);
-    let max: u32 = match env::var("MAX_POOL") {
-        Ok(max_) => from_str::<u32>(&max_).unwrap(),
-        Err(_e) => 20,
-    };
-    Pool::builder().max_size(max).build(manager).unwrap()
+    let manager = ConnectionManager::<DbConnection>::new(database_url);
+    Pool::builder()
+        .max_size(parse_env("MAX_POOL", 20))
+        .min_idle(parse_env_opt("POOL_MIN_IDLE"))
+        .connection_timeout(Duration::from_secs(parse_env(
+            "POOL_CONNECTION_TIMEOUT",
+            30,
+        )))
+        .max_lifetime(parse_env_opt("POOL_MAX_LIFETIME").map(Duration::from_secs))
+        .idle_timeout(parse_env_opt("POOL_IDLE_TIMEOUT").map(Duration::from_secs))
+        .test_on_check_out(parse_env("POOL_TEST_ON_CHECKOUT", true))
+        .build_unchecked(manager)
+}
+
+pub fn get_connection() -> Result<SqlConnection, Error> {
+    POOL.get().map_err(|e| {
+        error!("Database pool connection error: {}", e.to_string());
+        Error::DatabaseQueryError(e.to_string())
+    })
 }
 
 const PAGE_SIZE: i64 = 1000;
 #[derive(Serialize, Deserialize, Queryable)]
 struct Registry {
     pub lock_hash: String,
-    pub ccid:      u64,
+    pub ccid:      SqlU64,
 }
-pub fn get_registered_lock_hashes_and_ccids() -> Result<Vec<(H256, u64)>, Error> {
-    let conn = &POOL.clone().get().expect("Mysql pool connection error");
+/// Load the registered lock hashes and ccids, only the ones registered no later than the block
+/// number if it is given.
+pub fn get_registered_lock_hashes_and_ccids(
+    to_block_number: Option<u64>,
+) -> Result<Vec<(H256, u64)>, Error> {
+    let conn = &get_connection()?;
     let mut leaves: Vec<(H256, u64)> = Vec::new();
     let mut page: i64 = 0;
     loop {
-        let leaves_page = register_cota_kv_pairs
+        let mut query = register_cota_kv_pairs
             .select((lock_hash, cota_cell_id))
+            .order(id.asc())
             .limit(PAGE_SIZE)
             .offset(PAGE_SIZE * page)
-            .load::<Registry>(conn)
-            .map_or_else(
-                |e| {
-                    error!("Query registry lock hash and ccid error: {}", e.to_string());
-                    Err(Error::DatabaseQueryError(e.to_string()))
-                },
-                |registries| Ok(parse_registries(registries)),
-            )?;
+            .into_boxed();
+        if let Some(to_block_number) = to_block_number {
+            query = query.filter(registry_block_number.le(from_u64(to_block_number)));
+        }
+        let leaves_page = query.load::<Registry>(conn).map_or_else(
+            |e| {
+                error!("Query registry lock hash and ccid error: {}", e.to_string());
+                Err(Error::DatabaseQueryError(e.to_string()))
+            },
+            |registries| Ok(parse_registries(registries)),
+        )?;
         let length = leaves_page.len();
         leaves.extend(leaves_page);
         if length < (PAGE_SIZE as usize) {
@@ -57,13 +143,66 @@ pub fn get_registered_lock_hashes_and_ccids() -> Result<Vec<(H256, u64)>, Error>
     Ok(leaves)
 }
 
+#[derive(Queryable)]
+struct RegistryRow {
+    pub id:           i64,
+    pub lock_hash:    String,
+    pub ccid:         SqlU64,
+    pub block_number: SqlU64,
+}
+
+pub struct RegisteredLockHash {
+    pub id:           i64,
+    pub lock_hash:    [u8; 32],
+    pub ccid:         u64,
+    pub block_number: u64,
+}
+
+/// Page through the registered lock hashes in the order of id, the cursor is the id of the last
+/// lock hash of the previous page and the block number range is inclusive.
+pub fn get_registered_lock_hashes(
+    cursor: Option<i64>,
+    limit: i64,
+    from_block_number: Option<u64>,
+    to_block_number: Option<u64>,
+) -> Result<Vec<RegisteredLockHash>, Error> {
+    let conn = &get_connection()?;
+    let mut query = register_cota_kv_pairs
+        .select((id, lock_hash, cota_cell_id, registry_block_number))
+        .order(id.asc())
+        .limit(limit)
+        .into_boxed();
+    if let Some(cursor) = cursor {
+        query = query.filter(id.gt(cursor));
+    }
+    if let Some(from_block_number) = from_block_number {
+        query = query.filter(registry_block_number.ge(from_u64(from_block_number)));
+    }
+    if let Some(to_block_number) = to_block_number {
+        query = query.filter(registry_block_number.le(from_u64(to_block_number)));
+    }
+    let rows = query.load::<RegistryRow>(conn).map_err(|e| {
+        error!("Query registered lock hashes error: {}", e.to_string());
+        Error::DatabaseQueryError(e.to_string())
+    })?;
+    Ok(rows
+        .into_iter()
+        .map(|row| RegisteredLockHash {
+            id:           row.id,
+            lock_hash:    parse_bytes_n::<32>(row.lock_hash).unwrap(),
+            ccid:         to_u64(row.ccid),
+            block_number: to_u64(row.block_number),
+        })
+        .collect())
+}
+
 pub fn check_lock_hashes_registered(lock_hashes: Vec<[u8; 32]>) -> Result<(bool, u64), Error> {
-    let conn = &POOL.clone().get().expect("Mysql pool connection error");
+    let conn = &get_connection()?;
     let lock_hash_vec: Vec<String> = lock_hashes.iter().map(hex::encode).collect();
     let ccids = register_cota_kv_pairs
         .select(cota_cell_id)
         .filter(lock_hash.eq_any(lock_hash_vec))
-        .load::<u64>(conn)
+        .load::<SqlU64>(conn)
         .map_err(|e| {
             error!("Query registry state error: {}", e.to_string());
             Error::DatabaseQueryError(e.to_string())
@@ -73,12 +212,147 @@ pub fn check_lock_hashes_registered(lock_hashes: Vec<[u8; 32]>) -> Result<(bool,
     Ok((registered, block_height))
 }
 
+/// Load the ccids of the lock hashes which have been registered
+pub fn get_registered_ccids(lock_hashes: Vec<[u8; 32]>) -> Result<Vec<(H256, u64)>, Error> {
+    let conn = &get_connection()?;
+    let lock_hash_vec: Vec<String> = lock_hashes.iter().map(hex::encode).collect();
+    register_cota_kv_pairs
+        .select((lock_hash, cota_cell_id))
+        .filter(lock_hash.eq_any(lock_hash_vec))
+        .load::<Registry>(conn)
+        .map_or_else(
+            |e| {
+                error!("Query registered ccids error: {}", e.to_string());
+                Err(Error::DatabaseQueryError(e.to_string()))
+            },
+            |registries| Ok(parse_registries(registries)),
+        )
+}
+
+pub fn get_registered_count() -> Result<u64, Error> {
+    let conn = &get_connection()?;
+    register_cota_kv_pairs
+        .select(count_star())
+        .first::<i64>(conn)
+        .map(|count| count as u64)
+        .map_err(|e| {
+            error!("Query registered count error: {}", e.to_string());
+            Error::DatabaseQueryError(e.to_string())
+        })
+}
+
+#[derive(QueryableByName)]
+struct RegistrationsVersion {
+    #[sql_type = "BigInt"]
+    count:      i64,
+    #[sql_type = "Nullable<Text>"]
+    updated_at: Option<String>,
+}
+
+/// The count and the latest update time of the registrations no later than the block number,
+/// which change if any of the registrations is added, removed or has its `cota_cell_id` updated
+pub fn get_registrations_version(to_block_number: u64) -> Result<(u64, Option<String>), Error> {
+    let conn = &get_connection()?;
+    let query = format!(
+        "SELECT COUNT(*) AS count, CAST(MAX(updated_at) AS {}) AS updated_at FROM \
+         register_cota_kv_pairs WHERE block_number <= {}",
+        TEXT_CAST_TYPE,
+        from_u64(to_block_number)
+    );
+    sql_query(query)
+        .get_result::<RegistrationsVersion>(conn)
+        .map(|version| (version.count as u64, version.updated_at))
+        .map_err(|e| {
+            error!("Query registrations version error: {}", e.to_string());
+            Error::DatabaseQueryError(e.to_string())
+        })
+}
+
+// The start block number of the block range of every registration
+#[cfg(feature = "mysql")]
+fn block_range_start(range_size: i64) -> String {
+    format!(
+        "CAST(block_number DIV {size} * {size} AS SIGNED)",
+        size = range_size
+    )
+}
+
+#[cfg(not(feature = "mysql"))]
+fn block_range_start(range_size: i64) -> String {
+    format!("block_number / {size} * {size}", size = range_size)
+}
+
+#[derive(QueryableByName)]
+struct BlockRangeRegistrations {
+    #[sql_type = "BigInt"]
+    range_start: i64,
+    #[sql_type = "BigInt"]
+    count:       i64,
+}
+
+/// Count the registrations in the block ranges of `range_size` blocks, the ranges without
+/// registrations are omitted.
+pub fn get_registrations_per_block_range(range_size: u64) -> Result<Vec<(u64, u64)>, Error> {
+    let conn = &get_connection()?;
+    // The ranges larger than i64::MAX blocks are all the same single range
+    let range_size = range_size.min(i64::MAX as u64) as i64;
+    let query = format!(
+        "SELECT {start} AS range_start, COUNT(*) AS count FROM register_cota_kv_pairs GROUP BY \
+         {start} ORDER BY range_start",
+        start = block_range_start(range_size)
+    );
+    sql_query(query)
+        .load::<BlockRangeRegistrations>(conn)
+        .map(|ranges| {
+            ranges
+                .into_iter()
+                .map(|range| (range.range_start as u64, range.count as u64))
+                .collect()
+        })
+        .map_err(|e| {
+            error!(
+                "Query registrations per block range error: {}",
+                e.to_string()
+            );
+            Error::DatabaseQueryError(e.to_string())
+        })
+}
+
+#[derive(QueryableByName)]
+struct DailyRegistrations {
+    #[sql_type = "Text"]
+    day:   String,
+    #[sql_type = "BigInt"]
+    count: i64,
+}
+
+pub fn get_registrations_per_day() -> Result<Vec<(String, u64)>, Error> {
+    let conn = &get_connection()?;
+    let query = format!(
+        "SELECT {day} AS day, COUNT(*) AS count FROM register_cota_kv_pairs GROUP BY {day} ORDER \
+         BY day",
+        day = REGISTRATION_DAY
+    );
+    sql_query(query)
+        .load::<DailyRegistrations>(conn)
+        .map(|days| {
+            days.into_iter()
+                .map(|daily| (daily.day, daily.count as u64))
+                .collect()
+        })
+        .map_err(|e| {
+            error!("Query registrations per day error: {}", e.to_string());
+            Error::DatabaseQueryError(e.to_string())
+        })
+}
+
 pub fn get_syncer_tip_block_number() -> Result<u64, Error> {
-    let conn = &POOL.clone().get().expect("Mysql pool connection error");
+    let conn = &get_connection()?;
     check_infos
         .select(block_number)
         .order(block_number.desc())
-        .first::<u64>(conn)
+        .first::<SqlU64>(conn)
+        .map(to_u64)
         .map_err(|e| {
             error!("Query block number error: {}", e.to_string());
             Error::DatabaseQueryError(e.to_string())
@@ -91,7 +365,7 @@ fn parse_registries(registries: Vec<Registry>) -> Vec<(H256, u64)> {
         .map(|registry| {
             (
                 H256::from(parse_bytes_n::<32>(registry.lock_hash).unwrap()),
-                registry.ccid,
+                to_u64(registry.ccid),
             )
         })
         .collect()
@@ -1,5 +1,9 @@
 use failure::Fail;
-use jsonrpc_http_server::jsonrpc_core::{Error as RpcError, ErrorCode};
+use jsonrpc_http_server::jsonrpc_core::{Error as RpcError, ErrorCode, Value};
+use serde_json::json;
+
+/// The error code of the requests rejected for the API key
+pub const API_KEY_ERROR_CODE: i64 = -32001;
 
 #[derive(Debug, Fail, Eq, PartialEq)]
 pub enum Error {
@@ -9,6 +13,21 @@ pub enum Error {
     )]
     RequestParamHexInvalid(String),
 
+    #[fail(display = "Request params error: {}", _0)]
+    RequestParamsInvalid(String),
+
+    #[fail(display = "Some request params are invalid")]
+    RequestParamElementsInvalid(Vec<(usize, String)>),
+
+    #[fail(display = "Request address error: {}", _0)]
+    RequestParamAddressInvalid(String),
+
+    #[fail(display = "Request lock script '{}' is invalid", _0)]
+    RequestParamLockScriptInvalid(String),
+
+    #[fail(display = "Request registry '{}' is invalid", _0)]
+    RequestParamRegistryInvalid(String),
+
     #[fail(display = "The lock_hash has registered")]
     LockHashHasRegistered,
 
@@ -23,6 +42,27 @@ pub enum Error {
 
     #[fail(display = "'{}' RocksDB error", _0)]
     RocksDBError(String),
+
+    #[fail(display = "Database migration error: {}", _0)]
+    DatabaseMigrationError(String),
+
+    #[fail(display = "Request timeout after {}s", _0)]
+    RequestTimeout(u64),
+
+    #[fail(display = "Blocking task error: {}", _0)]
+    BlockingTaskError(String),
+
+    #[fail(display = "Registry entry error: {}", _0)]
+    RegistryEntryError(String),
+
+    #[fail(display = "Config error: {}", _0)]
+    ConfigError(String),
+
+    #[fail(display = "API key is missing or invalid")]
+    ApiKeyInvalid,
+
+    #[fail(display = "API key quota exceeded: {}", _0)]
+    ApiKeyQuotaExceeded(String),
 }
 
 impl Error {
@@ -32,21 +72,55 @@ impl Error {
                 "Request lock_hash '{}' must be 32bytes hex string starting with 0x",
                 msg
             ),
+            Self::RequestParamsInvalid(msg) => format!("Request params error: {}", msg),
+            Self::RequestParamElementsInvalid(invalid_params) => {
+                format!("{} request params are invalid", invalid_params.len())
+            }
+            Self::RequestParamAddressInvalid(msg) => format!("Request address error: {}", msg),
+            Self::RequestParamLockScriptInvalid(msg) => {
+                format!("Request lock script '{}' is invalid", msg)
+            }
+            Self::RequestParamRegistryInvalid(msg) => {
+                format!("Request registry '{}' is invalid", msg)
+            }
             Self::LockHashHasRegistered => "The lock_hash has registered".to_string(),
             Self::CKBIndexerError(msg) => format!("CKB Indexer error: {}", msg),
             Self::DatabaseQueryError(msg) => format!("Database query error: {}", msg),
             Self::SMTError(msg) => format!("SMT error: {}", msg),
             Self::RocksDBError(msg) => format!("RocksDB error: {}", msg),
+            Self::DatabaseMigrationError(msg) => format!("Database migration error: {}", msg),
+            Self::RequestTimeout(secs) => format!("Request timeout after {}s", secs),
+            Self::BlockingTaskError(msg) => format!("Blocking task error: {}", msg),
+            Self::RegistryEntryError(msg) => format!("Registry entry error: {}", msg),
+            Self::ConfigError(msg) => format!("Config error: {}", msg),
+            Self::ApiKeyInvalid => "API key is missing or invalid".to_string(),
+            Self::ApiKeyQuotaExceeded(msg) => format!("API key quota exceeded: {}", msg),
         }
     }
 }
 
 impl Into<RpcError> for Error {
     fn into(self) -> RpcError {
+        // The index and the error message of every invalid request param
+        let data = match &self {
+            Self::RequestParamElementsInvalid(invalid_params) => Some(Value::Array(
+                invalid_params
+                    .iter()
+                    .map(|(index, msg)| json!({ "index": index, "message": msg }))
+                    .collect(),
+            )),
+            _ => None,
+        };
+        let code = match &self {
+            Self::ApiKeyInvalid | Self::ApiKeyQuotaExceeded(_) => {
+                ErrorCode::ServerError(API_KEY_ERROR_CODE)
+            }
+            _ => ErrorCode::InvalidParams,
+        };
         RpcError {
-            code:    ErrorCode::InvalidParams,
+            code,
             message: self.to_msg(),
-            data:    None,
+            data,
         }
     }
 }
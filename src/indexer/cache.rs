@@ -0,0 +1,69 @@
+use crate::config::get_config;
+use crate::error::Error;
+use crate::indexer::index::{get_registry_cell, RegistryCell};
+use crate::utils::parse_env;
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use parking_lot::RwLock;
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+
+lazy_static! {
+    static ref REGISTRY_CELL: RwLock<Option<CachedRegistryCell>> = RwLock::new(None);
+}
+
+struct CachedRegistryCell {
+    cell:       RegistryCell,
+    fetched_at: Instant,
+}
+
+/// Return the cached registry cell if it is younger than `REGISTRY_CELL_TTL`, otherwise query it
+/// from the CKB indexer. The registrations fetch the live registry cell by
+/// `refresh_registry_cell` only if the cached root doesn't match the RocksDB SMT.
+pub async fn get_cached_registry_cell() -> Result<RegistryCell, Error> {
+    let cached_cell = REGISTRY_CELL
+        .read()
+        .as_ref()
+        .filter(|cached| cached.fetched_at.elapsed() < get_config().registry_cell_ttl)
+        .map(|cached| cached.cell.clone());
+    match cached_cell {
+        Some(cell) => Ok(cell),
+        None => refresh_registry_cell().await,
+    }
+}
+
+pub async fn refresh_registry_cell() -> Result<RegistryCell, Error> {
+    let cell = get_registry_cell().await?;
+    let mut cached = REGISTRY_CELL.write();
+    if cached.as_ref().map(|cached| &cached.cell.out_point) != Some(&cell.out_point) {
+        debug!("Registry cell changes to {:?}", cell.out_point);
+    }
+    *cached = Some(CachedRegistryCell {
+        cell:       cell.clone(),
+        fetched_at: Instant::now(),
+    });
+    Ok(cell)
+}
+
+pub fn invalidate_registry_cell() {
+    debug!("Invalidate the cached registry cell");
+    *REGISTRY_CELL.write() = None;
+}
+
+/// Refresh the cached registry cell every `REGISTRY_CELL_POLL_INTERVAL` seconds, 0 to disable,
+/// and it is disabled by default as the registrations fetch the live cell on a root mismatch.
+pub fn spawn_registry_cell_poller(handle: &Handle) {
+    let interval: u64 = parse_env("REGISTRY_CELL_POLL_INTERVAL", 0);
+    if interval == 0 {
+        return;
+    }
+    handle.spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+        loop {
+            ticker.tick().await;
+            if let Err(err) = refresh_registry_cell().await {
+                warn!("Refresh registry cell error: {}", err.to_msg());
+            }
+        }
+    });
+}
@@ -0,0 +1,191 @@
+use crate::error::Error;
+use crate::utils::parse_env;
+use jsonrpc_http_server::jsonrpc_core;
+use lazy_static::lazy_static;
+use log::warn;
+use serde_json::{json, Map, Value};
+use std::env;
+use std::time::Duration;
+use tokio::time::sleep;
+
+// The backoff stops doubling at this cap so that many retries can't overflow or sleep forever
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    pub static ref INDEXER_CLIENT: IndexerClient = IndexerClient::from_env("CKB_INDEXER");
+    pub static ref NODE_CLIENT: IndexerClient = IndexerClient::from_env("CKB_NODE");
+}
+
+enum CallError {
+    // Connection errors, timeouts and 5xx responses which are worth retrying on the same endpoint
+    Transient(String),
+    // Unexpected responses which make the endpoint unusable for this call
+    Endpoint(String),
+    // Errors returned by the JSON-RPC method itself
+    Rpc(String),
+}
+
+/// The JSON-RPC client shared by all the requests to the CKB indexers and nodes, it retries the
+/// transient errors with exponential backoff and fails over to the next endpoint.
+pub struct IndexerClient {
+    url_key:   String,
+    client:    reqwest::Client,
+    endpoints: Vec<String>,
+    retries:   u32,
+    backoff:   Duration,
+}
+
+impl IndexerClient {
+    pub fn new(
+        url_key: &str,
+        endpoints: Vec<String>,
+        timeout: Duration,
+        retries: u32,
+        backoff: Duration,
+    ) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .connect_timeout(timeout)
+            .build()
+            .expect("CKB indexer client build error");
+        IndexerClient {
+            url_key: url_key.to_owned(),
+            client,
+            endpoints,
+            retries,
+            backoff,
+        }
+    }
+
+    /// The url env accepts a comma separated list of endpoints in the order of preference.
+    pub fn from_env(url_key: &str) -> Self {
+        let endpoints = env::var(url_key)
+            .map(|urls| {
+                urls.split(',')
+                    .map(|url| url.trim().to_owned())
+                    .filter(|url| !url.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        IndexerClient::new(
+            url_key,
+            endpoints,
+            Duration::from_secs(parse_env("CKB_INDEXER_TIMEOUT", 10)),
+            parse_env("CKB_INDEXER_RETRIES", 3),
+            Duration::from_millis(parse_env("CKB_INDEXER_BACKOFF", 200)),
+        )
+    }
+
+    pub fn is_configured(&self) -> bool {
+        !self.endpoints.is_empty()
+    }
+
+    pub async fn call(&self, method: &str, params: Value) -> Result<Value, Error> {
+        if !self.is_configured() {
+            return Err(Error::CKBIndexerError(format!(
+                "{} must be set",
+                self.url_key
+            )));
+        }
+        let mut req_json = Map::new();
+        req_json.insert("id".to_owned(), json!("1"));
+        req_json.insert("jsonrpc".to_owned(), json!("2.0"));
+        req_json.insert("method".to_owned(), json!(method));
+        req_json.insert("params".to_owned(), params);
+
+        let mut last_error = String::new();
+        for endpoint in self.endpoints.iter() {
+            for attempt in 0..=self.retries {
+                if attempt > 0 {
+                    sleep(self.retry_backoff(attempt)).await;
+                }
+                match self.post(endpoint, &req_json).await {
+                    Ok(result) => return Ok(result),
+                    Err(CallError::Rpc(msg)) => return Err(Error::CKBIndexerError(msg)),
+                    Err(CallError::Transient(msg)) => {
+                        warn!(
+                            "CKB indexer {} {} attempt {} error: {}",
+                            endpoint, method, attempt, msg
+                        );
+                        last_error = msg;
+                    }
+                    Err(CallError::Endpoint(msg)) => {
+                        warn!("CKB indexer {} {} error: {}", endpoint, method, msg);
+                        last_error = msg;
+                        break;
+                    }
+                }
+            }
+        }
+        Err(Error::CKBIndexerError(last_error))
+    }
+
+    fn retry_backoff(&self, attempt: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(MAX_BACKOFF)
+    }
+
+    async fn post(
+        &self,
+        endpoint: &str,
+        req_json: &Map<String, Value>,
+    ) -> Result<Value, CallError> {
+        let resp = self
+            .client
+            .post(endpoint)
+            .json(req_json)
+            .send()
+            .await
+            .map_err(|e| {
+                if e.is_timeout() || e.is_connect() || e.is_request() {
+                    CallError::Transient(e.to_string())
+                } else {
+                    CallError::Endpoint(e.to_string())
+                }
+            })?;
+        let status = resp.status();
+        if status.is_server_error() {
+            return Err(CallError::Transient(format!("HTTP status {}", status)));
+        }
+        if !status.is_success() {
+            return Err(CallError::Endpoint(format!("HTTP status {}", status)));
+        }
+        let output = resp
+            .json::<jsonrpc_core::response::Output>()
+            .await
+            .map_err(|e| {
+                if e.is_timeout() {
+                    CallError::Transient(e.to_string())
+                } else {
+                    CallError::Endpoint(e.to_string())
+                }
+            })?;
+        match output {
+            jsonrpc_core::response::Output::Success(success) => Ok(success.result),
+            jsonrpc_core::response::Output::Failure(failure) => {
+                Err(CallError::Rpc(failure.error.message))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_backoff() {
+        let client = IndexerClient::new(
+            "CKB_INDEXER",
+            vec![],
+            Duration::from_secs(1),
+            100,
+            Duration::from_millis(200),
+        );
+        assert_eq!(client.retry_backoff(1), Duration::from_millis(200));
+        assert_eq!(client.retry_backoff(3), Duration::from_millis(800));
+        assert_eq!(client.retry_backoff(40), MAX_BACKOFF);
+        assert_eq!(client.retry_backoff(u32::MAX), MAX_BACKOFF);
+    }
+}
@@ -1,8 +1,9 @@
 use crate::error::Error;
-use ckb_jsonrpc_types::{BlockNumber, CellOutput, JsonBytes, OutPoint, Uint32};
-use jsonrpc_http_server::jsonrpc_core;
+use crate::indexer::client::{IndexerClient, INDEXER_CLIENT, NODE_CLIENT};
+use crate::utils::is_mainnet;
+use ckb_jsonrpc_types::{BlockNumber, CellOutput, CellWithStatus, JsonBytes, OutPoint, Uint32};
 use serde::Deserialize;
-use serde_json::{from_str, json, Map, Value};
+use serde_json::{json, Value};
 use std::env;
 
 const TESTNET_REGISTRY_COTA_CODE_HASH: &str =
@@ -12,8 +13,15 @@ const MAINNET_REGISTRY_COTA_CODE_HASH: &str =
     "0x90ca618be6c15f5857d3cbd09f9f24ca6770af047ba9ee70989ec3b229419ac7";
 const MAINNET_REGISTRY_COTA_ARGS: &str = "0x563631b49cee549f3585ab4dde5f9d590f507f1f";
 
+// Only the version 0 of the registry cell data is defined by the registry contract
+const REGISTRY_VERSIONS: [u8; 1] = [0];
+
+/// The registry cell data is `version(1 byte) | smt_root(32 bytes) | extension(8 bytes, optional)`
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct RegistryInfo {
-    pub smt_root: [u8; 32],
+    pub version:   u8,
+    pub smt_root:  [u8; 32],
+    pub extension: Option<[u8; 8]>,
 }
 
 impl RegistryInfo {
@@ -23,62 +31,109 @@ impl RegistryInfo {
                 "Registry cell data length error".to_owned(),
             ));
         }
+        let version = data[0];
+        if !REGISTRY_VERSIONS.contains(&version) {
+            return Err(Error::CKBIndexerError(format!(
+                "Registry cell data version {} is not supported",
+                version
+            )));
+        }
         let mut smt_root = [0u8; 32];
         smt_root.copy_from_slice(&data[1..33]);
+        let extension = if data.len() == 41 {
+            let mut extension = [0u8; 8];
+            extension.copy_from_slice(&data[33..41]);
+            Some(extension)
+        } else {
+            None
+        };
 
-        let registry = RegistryInfo { smt_root };
+        let registry = RegistryInfo {
+            version,
+            smt_root,
+            extension,
+        };
         Ok(registry)
     }
 }
 
-pub async fn get_registry_info() -> Result<RegistryInfo, Error> {
-    let ckb_indexer_url = env::var("CKB_INDEXER")
-        .map_err(|_e| Error::CKBIndexerError("CKB_INDEXER must be set".to_owned()))?;
-
-    let mut req_json = Map::new();
-    req_json.insert("id".to_owned(), json!("1"));
-    req_json.insert("jsonrpc".to_owned(), json!("2.0"));
-    req_json.insert("method".to_owned(), json!("get_cells"));
-    req_json.insert("params".to_owned(), generate_params()?);
-
-    let client = reqwest::Client::new();
-
-    let resp = client
-        .post(ckb_indexer_url)
-        .json(&req_json)
-        .send()
-        .await
-        .map_err(|e| Error::CKBIndexerError(e.to_string()))?;
-    let output = resp
-        .json::<jsonrpc_core::response::Output>()
-        .await
-        .map_err(|e| Error::CKBIndexerError(e.to_string()))?;
-
-    let result: CellPagination = match output {
-        jsonrpc_core::response::Output::Success(success) => {
-            serde_json::from_value::<CellPagination>(success.result)
-                .map_err(|_e| Error::CKBIndexerError("Parse response error".to_owned()))
-        }
-        jsonrpc_core::response::Output::Failure(failure) => {
-            Err(Error::CKBIndexerError(failure.error.message))
+#[derive(Clone)]
+pub struct RegistryCell {
+    pub info:         RegistryInfo,
+    pub out_point:    OutPoint,
+    pub block_number: u64,
+}
+
+pub async fn get_registry_cell() -> Result<RegistryCell, Error> {
+    // The built-in indexer of CKB node accepts the same get_cells params as ckb-indexer
+    let cells_client = match env::var("INDEXER_MODE").as_deref() {
+        Ok("node") => &*NODE_CLIENT,
+        Ok("standalone") | Err(_) => &*INDEXER_CLIENT,
+        Ok(mode) => {
+            return Err(Error::CKBIndexerError(format!(
+                "Unknown INDEXER_MODE '{}'",
+                mode
+            )))
         }
-    }?;
+    };
+    let node_client = Some(&*NODE_CLIENT).filter(|client| client.is_configured());
+    fetch_registry_cell(cells_client, node_client).await
+}
+
+pub async fn fetch_registry_cell(
+    cells_client: &IndexerClient,
+    node_client: Option<&IndexerClient>,
+) -> Result<RegistryCell, Error> {
+    let result = cells_client.call("get_cells", generate_params()?).await?;
+    let mut result: CellPagination = serde_json::from_value::<CellPagination>(result)
+        .map_err(|_e| Error::CKBIndexerError("Parse response error".to_owned()))?;
     if result.objects.is_empty() {
         return Err(Error::CKBIndexerError(
             "Registry cell data error".to_owned(),
         ));
     }
-    let cell_data = result.objects.first().unwrap().output_data.as_bytes();
-    RegistryInfo::from(cell_data)
+    if result.objects.len() > 1 {
+        return Err(Error::CKBIndexerError(
+            "More than one registry cell match the registry type script".to_owned(),
+        ));
+    }
+    let cell = result.objects.remove(0);
+    if let Some(node_client) = node_client {
+        check_live_cell(node_client, &cell).await?;
+    }
+    Ok(RegistryCell {
+        info:         RegistryInfo::from(cell.output_data.as_bytes())?,
+        out_point:    cell.out_point,
+        block_number: cell.block_number.value(),
+    })
 }
 
-fn generate_params() -> Result<Value, Error> {
-    let is_mainnet: bool = match env::var("IS_MAINNET") {
-        Ok(mainnet) => from_str::<bool>(&mainnet).unwrap(),
-        Err(_e) => false,
-    };
+async fn check_live_cell(node_client: &IndexerClient, cell: &Cell) -> Result<(), Error> {
+    let result = node_client
+        .call("get_live_cell", json!([cell.out_point, true]))
+        .await?;
+    let cell_with_status = serde_json::from_value::<CellWithStatus>(result)
+        .map_err(|_e| Error::CKBIndexerError("Parse live cell error".to_owned()))?;
+    if cell_with_status.status != "live" {
+        return Err(Error::CKBIndexerError(format!(
+            "Registry cell status is {}",
+            cell_with_status.status
+        )));
+    }
+    let live_data = cell_with_status
+        .cell
+        .and_then(|cell_info| cell_info.data)
+        .map(|cell_data| cell_data.content);
+    if live_data.as_ref().map(|data| data.as_bytes()) != Some(cell.output_data.as_bytes()) {
+        return Err(Error::CKBIndexerError(
+            "Registry cell data is different from the live cell".to_owned(),
+        ));
+    }
+    Ok(())
+}
 
-    let (code_hash, args) = if is_mainnet {
+fn generate_params() -> Result<Value, Error> {
+    let (code_hash, args) = if is_mainnet() {
         (MAINNET_REGISTRY_COTA_CODE_HASH, MAINNET_REGISTRY_COTA_ARGS)
     } else {
         (TESTNET_REGISTRY_COTA_CODE_HASH, TESTNET_REGISTRY_COTA_ARGS)
@@ -94,21 +149,19 @@ fn generate_params() -> Result<Value, Error> {
             "script_type": "type",
         },
         "asc",
-        "0x1"
+        "0x2"
     ]))
 }
 
 #[derive(Deserialize)]
 struct Cell {
     #[serde(skip_deserializing)]
-    _output:       CellOutput,
-    output_data:   JsonBytes,
-    #[serde(skip_deserializing)]
-    _out_point:    OutPoint,
-    #[serde(skip_deserializing)]
-    _block_number: BlockNumber,
+    _output:      CellOutput,
+    output_data:  JsonBytes,
+    out_point:    OutPoint,
+    block_number: BlockNumber,
     #[serde(skip_deserializing)]
-    _tx_index:     Uint32,
+    _tx_index:    Uint32,
 }
 
 #[derive(Deserialize)]
@@ -117,3 +170,155 @@ struct CellPagination {
     #[serde(skip_deserializing)]
     _last_cursor: JsonBytes,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::block_on;
+    use crate::test_support::mock_indexer::{
+        registry_cell_data, MockIndexer, MockResponse, REGISTRY_BLOCK_NUMBER, REGISTRY_TX_HASH,
+    };
+    use std::time::Duration;
+
+    fn new_client(endpoints: Vec<String>) -> IndexerClient {
+        IndexerClient::new(
+            "CKB_INDEXER",
+            endpoints,
+            Duration::from_secs(2),
+            1,
+            Duration::from_millis(10),
+        )
+    }
+
+    fn indexer_error(msg: &str) -> Error {
+        Error::CKBIndexerError(msg.to_owned())
+    }
+
+    #[test]
+    fn test_registry_info_from() {
+        let mut data = vec![0u8];
+        data.extend_from_slice(&[1u8; 32]);
+        assert_eq!(
+            RegistryInfo::from(&data),
+            Ok(RegistryInfo {
+                version:   0,
+                smt_root:  [1u8; 32],
+                extension: None,
+            })
+        );
+
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 8]);
+        assert_eq!(
+            RegistryInfo::from(&data),
+            Ok(RegistryInfo {
+                version:   0,
+                smt_root:  [1u8; 32],
+                extension: Some([0, 0, 0, 0, 0, 0, 0, 8]),
+            })
+        );
+
+        data[0] = 1;
+        assert_eq!(
+            RegistryInfo::from(&data),
+            Err(Error::CKBIndexerError(
+                "Registry cell data version 1 is not supported".to_owned()
+            ))
+        );
+
+        assert_eq!(
+            RegistryInfo::from(&data[0..40]),
+            Err(Error::CKBIndexerError(
+                "Registry cell data length error".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_fetch_registry_cell() {
+        let indexer = MockIndexer::start(MockResponse::Cells(vec![registry_cell_data([1u8; 32])]));
+        let client = new_client(vec![indexer.url()]);
+        let cell = block_on(fetch_registry_cell(&client, None)).unwrap();
+        assert_eq!(cell.info.smt_root, [1u8; 32]);
+        assert_eq!(cell.block_number, REGISTRY_BLOCK_NUMBER);
+        assert_eq!(
+            serde_json::to_value(&cell.out_point).unwrap()["tx_hash"],
+            json!(REGISTRY_TX_HASH)
+        );
+
+        let cell = block_on(fetch_registry_cell(&client, Some(&client))).unwrap();
+        assert_eq!(cell.info.smt_root, [1u8; 32]);
+    }
+
+    #[test]
+    fn test_fetch_registry_cell_error() {
+        let indexer = MockIndexer::start(MockResponse::Cells(vec![]));
+        let client = new_client(vec![indexer.url()]);
+        assert_eq!(
+            block_on(fetch_registry_cell(&client, None)).err(),
+            Some(indexer_error("Registry cell data error"))
+        );
+
+        indexer.set_response(MockResponse::Cells(vec![
+            registry_cell_data([1u8; 32]),
+            registry_cell_data([2u8; 32]),
+        ]));
+        assert_eq!(
+            block_on(fetch_registry_cell(&client, None)).err(),
+            Some(indexer_error(
+                "More than one registry cell match the registry type script"
+            ))
+        );
+
+        indexer.set_response(MockResponse::Cells(vec![vec![0u8; 20]]));
+        assert_eq!(
+            block_on(fetch_registry_cell(&client, None)).err(),
+            Some(indexer_error("Registry cell data length error"))
+        );
+
+        indexer.set_response(MockResponse::Malformed);
+        assert_eq!(
+            block_on(fetch_registry_cell(&client, None)).err(),
+            Some(indexer_error("Parse response error"))
+        );
+    }
+
+    #[test]
+    fn test_fetch_registry_cell_failure_without_retry() {
+        let indexer = MockIndexer::start(MockResponse::Failure("Indexer is syncing".to_owned()));
+        let client = new_client(vec![indexer.url()]);
+        assert_eq!(
+            block_on(fetch_registry_cell(&client, None)).err(),
+            Some(indexer_error("Indexer is syncing"))
+        );
+        assert_eq!(indexer.requests(), 1);
+    }
+
+    #[test]
+    fn test_fetch_registry_cell_failover() {
+        let indexer = MockIndexer::start(MockResponse::Cells(vec![registry_cell_data([1u8; 32])]));
+        let client = new_client(vec!["http://127.0.0.1:1".to_owned(), indexer.url()]);
+        let cell = block_on(fetch_registry_cell(&client, None)).unwrap();
+        assert_eq!(cell.info.smt_root, [1u8; 32]);
+        assert_eq!(indexer.requests(), 1);
+    }
+
+    #[test]
+    fn test_fetch_registry_cell_live_cell_mismatch() {
+        let indexer = MockIndexer::start(MockResponse::Cells(vec![registry_cell_data([1u8; 32])]));
+        let client = new_client(vec![indexer.url()]);
+
+        indexer.set_live_cell(Some(registry_cell_data([2u8; 32])), "live");
+        assert_eq!(
+            block_on(fetch_registry_cell(&client, Some(&client))).err(),
+            Some(indexer_error(
+                "Registry cell data is different from the live cell"
+            ))
+        );
+
+        indexer.set_live_cell(None, "dead");
+        assert_eq!(
+            block_on(fetch_registry_cell(&client, Some(&client))).err(),
+            Some(indexer_error("Registry cell status is dead"))
+        );
+    }
+}
@@ -2,21 +2,40 @@
 extern crate diesel;
 extern crate dotenv;
 
-use crate::api::{check_registered_rpc, register_rpc};
+use crate::admin::start_admin_server;
+use crate::api::build_rpc_handler;
+use crate::api_key::RequestMeta;
+use crate::audit::spawn_registry_auditor;
+use crate::config::init_config;
 use crate::db::{init_connection_pool, SqlConnectionPool};
+use crate::indexer::cache::spawn_registry_cell_poller;
+use crate::migration::{prepare_database, run_pending_migrations};
 use crate::smt::db::db::RocksDB;
+use crate::smt::entry::reconcile_registry_smt;
+use crate::smt::export::{export_registry_leaves, ExportFormat, ExportRange};
+use crate::smt::verify::decode_registry_entry;
+use crate::utils::parse_env;
 use dotenv::dotenv;
-use jsonrpc_http_server::jsonrpc_core::IoHandler;
 use jsonrpc_http_server::ServerBuilder;
 use lazy_static::lazy_static;
-use log::info;
+use log::{error, info};
+use std::{env, process};
 
+mod address;
+mod admin;
 mod api;
+mod api_key;
+mod audit;
+mod blocking;
+mod config;
 mod db;
 mod error;
 mod indexer;
+mod migration;
 mod schema;
 mod smt;
+#[cfg(test)]
+mod test_support;
 mod utils;
 
 lazy_static! {
@@ -30,14 +49,84 @@ fn main() {
         .format_timestamp(Some(env_logger::fmt::TimestampPrecision::Millis))
         .init();
 
-    let mut io = IoHandler::default();
-    io.add_method("register_cota_cells", register_rpc);
-    io.add_method("check_registered_lock_hashes", check_registered_rpc);
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(|command| command.as_str()) {
+        Some("migrate") => {
+            if let Err(err) = run_pending_migrations() {
+                error!("{}", err.to_msg());
+                process::exit(1);
+            }
+            return;
+        }
+        Some("reconcile") => {
+            let dry_run = args.get(2).map(|arg| arg.as_str()) == Some("--dry-run");
+            match reconcile_registry_smt(dry_run) {
+                Ok(report) => println!("{:#}", report.to_json(dry_run)),
+                Err(err) => {
+                    error!("{}", err.to_msg());
+                    process::exit(1);
+                }
+            }
+            return;
+        }
+        Some("export-leaves") => {
+            let format = args.get(2).map_or("jsonl", |format| format.as_str());
+            let range = args.get(3).map_or("", |range| range.as_str());
+            let result = format
+                .parse::<ExportFormat>()
+                .and_then(|format| export_registry_leaves(format, &range.parse::<ExportRange>()?));
+            match result {
+                Ok(count) => info!("Exported {} SMT leaves", count),
+                Err(err) => {
+                    error!("{}", err.to_msg());
+                    process::exit(1);
+                }
+            }
+            return;
+        }
+        Some("decode-registry-entry") => {
+            let entry = args.get(2).unwrap_or_else(|| {
+                eprintln!("Usage: cota-registry-aggregator decode-registry-entry <entry> [root]");
+                process::exit(1);
+            });
+            match decode_registry_entry(entry, args.get(3).map(|root| root.as_str())) {
+                Ok(entry_json) => println!("{:#}", entry_json),
+                Err(err) => {
+                    eprintln!("{}", err.to_msg());
+                    process::exit(1);
+                }
+            }
+            return;
+        }
+        _ => {}
+    }
 
-    let server = ServerBuilder::new(io)
-        .threads(3)
+    if let Err(err) = prepare_database() {
+        error!("{}", err.to_msg());
+        process::exit(1);
+    }
+    if let Err(err) = init_config() {
+        error!("{}", err.to_msg());
+        process::exit(1);
+    }
+
+    let io = build_rpc_handler();
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(parse_env("RPC_THREADS", 3))
+        .thread_name("rpc-worker")
+        .enable_all()
+        .build()
+        .expect("Tokio runtime build error");
+    spawn_registry_cell_poller(runtime.handle());
+    spawn_registry_auditor(runtime.handle());
+
+    let server = ServerBuilder::with_meta_extractor(io, RequestMeta::from_request)
+        .event_loop_executor(runtime.handle().clone())
+        .threads(1)
         .start_http(&"0.0.0.0:3050".parse().unwrap())
         .unwrap();
+    let admin_server = start_admin_server(runtime.handle());
 
     let version = env!("CARGO_PKG_VERSION");
     info!(
@@ -46,4 +135,7 @@ fn main() {
     );
 
     server.wait();
+    if let Some(admin_server) = admin_server {
+        admin_server.wait();
+    }
 }
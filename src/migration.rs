@@ -0,0 +1,143 @@
+use crate::db::{get_connection, DbConnection};
+use crate::error::Error;
+use diesel::connection::SimpleConnection;
+use diesel::sql_types::{BigInt, Text};
+use diesel::*;
+use log::{error, info};
+use std::env;
+
+#[cfg(feature = "mysql")]
+macro_rules! migration_sql {
+    ($name:literal) => {
+        include_str!(concat!("../migrations/mysql/", $name, "/up.sql"))
+    };
+}
+
+#[cfg(feature = "postgres")]
+macro_rules! migration_sql {
+    ($name:literal) => {
+        include_str!(concat!("../migrations/postgres/", $name, "/up.sql"))
+    };
+}
+
+#[cfg(feature = "sqlite")]
+macro_rules! migration_sql {
+    ($name:literal) => {
+        include_str!(concat!("../migrations/sqlite/", $name, "/up.sql"))
+    };
+}
+
+struct Migration {
+    name:    &'static str,
+    // The migration is applied only if any of the (table, column) is missing
+    columns: &'static [(&'static str, &'static str)],
+    up:      &'static str,
+}
+
+// The schema is shared with cota-nft-entries-syncer which owns the `schema_migrations` table, so
+// the schema state is detected from the columns rather than a version.
+const MIGRATIONS: [Migration; 2] = [
+    Migration {
+        name:    "2022-02-08-094146_init_schema",
+        columns: &[
+            ("check_infos", "block_hash"),
+            ("register_cota_kv_pairs", "lock_hash"),
+        ],
+        up:      migration_sql!("2022-02-08-094146_init_schema"),
+    },
+    Migration {
+        name:    "2022-09-09_add_ccid_to_registry",
+        columns: &[("register_cota_kv_pairs", "cota_cell_id")],
+        up:      migration_sql!("2022-09-09_add_ccid_to_registry"),
+    },
+];
+
+#[cfg(feature = "mysql")]
+const COLUMN_COUNT_QUERY: &str = "SELECT COUNT(*) AS count FROM information_schema.columns WHERE \
+                                  table_schema = DATABASE() AND table_name = ? AND column_name = ?";
+#[cfg(feature = "postgres")]
+const COLUMN_COUNT_QUERY: &str = "SELECT COUNT(*) AS count FROM information_schema.columns WHERE \
+                                  table_schema = current_schema() AND table_name = $1 AND \
+                                  column_name = $2";
+#[cfg(feature = "sqlite")]
+const COLUMN_COUNT_QUERY: &str =
+    "SELECT COUNT(*) AS count FROM pragma_table_info(?) WHERE name = ?";
+
+#[derive(QueryableByName)]
+struct ColumnCount {
+    #[sql_type = "BigInt"]
+    count: i64,
+}
+
+/// Prepare the database before the server starts according to `MIGRATION_MODE`:
+/// - `skip`(default): do nothing
+/// - `check`: refuse to start if any column the code expects is missing
+/// - `apply`: apply the migrations whose columns are missing and then check the schema
+pub fn prepare_database() -> Result<(), Error> {
+    let mode = env::var("MIGRATION_MODE").unwrap_or_else(|_| "skip".to_owned());
+    match mode.as_str() {
+        "check" => check_schema(),
+        "apply" => {
+            run_pending_migrations()?;
+            check_schema()
+        }
+        "skip" => Ok(()),
+        _ => Err(Error::DatabaseMigrationError(format!(
+            "Unknown MIGRATION_MODE '{}'",
+            mode
+        ))),
+    }
+}
+
+pub fn run_pending_migrations() -> Result<usize, Error> {
+    let conn = &get_connection()?;
+    let mut applied = 0;
+    for migration in MIGRATIONS.iter() {
+        if !is_pending(conn, migration)? {
+            continue;
+        }
+        info!("Apply migration {}", migration.name);
+        conn.batch_execute(migration.up)
+            .map_err(|e| migration_error(migration.name, e))?;
+        applied += 1;
+    }
+    info!("{} migrations have been applied", applied);
+    Ok(applied)
+}
+
+pub fn check_schema() -> Result<(), Error> {
+    let conn = &get_connection()?;
+    for migration in MIGRATIONS.iter() {
+        if is_pending(conn, migration)? {
+            return Err(Error::DatabaseMigrationError(format!(
+                "Database schema misses the migration {}, run the migrate command or set \
+                 MIGRATION_MODE=apply",
+                migration.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn is_pending(conn: &DbConnection, migration: &Migration) -> Result<bool, Error> {
+    for (table, column) in migration.columns.iter() {
+        if !column_exists(conn, table, column)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn column_exists(conn: &DbConnection, table: &str, column: &str) -> Result<bool, Error> {
+    sql_query(COLUMN_COUNT_QUERY)
+        .bind::<Text, _>(table)
+        .bind::<Text, _>(column)
+        .get_result::<ColumnCount>(conn)
+        .map(|column_count| column_count.count > 0)
+        .map_err(|e| migration_error("Query columns", e))
+}
+
+fn migration_error(step: &str, e: result::Error) -> Error {
+    error!("{} error: {}", step, e.to_string());
+    Error::DatabaseMigrationError(format!("{}: {}", step, e.to_string()))
+}
@@ -0,0 +1,16 @@
+//! The table definitions of the database shared with cota-nft-entries-syncer, one module per
+//! supported diesel backend.
+
+#[cfg(feature = "mysql")]
+mod mysql;
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+#[cfg(feature = "mysql")]
+pub use self::mysql::*;
+#[cfg(feature = "postgres")]
+pub use self::postgres::*;
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::*;
@@ -0,0 +1,30 @@
+table! {
+    check_infos (id) {
+        id -> Bigint,
+        check_type -> Smallint,
+        block_number -> Bigint,
+        block_hash -> Char,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    register_cota_kv_pairs (id) {
+        id -> Bigint,
+        block_number -> Bigint,
+        lock_hash -> Char,
+        cota_cell_id -> Bigint,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    schema_migrations (version) {
+        version -> Bigint,
+        dirty -> Bool,
+    }
+}
+
+allow_tables_to_appear_in_same_query!(check_infos, register_cota_kv_pairs, schema_migrations,);
@@ -2,11 +2,12 @@ use super::schema::Col;
 use crate::error::Error;
 use crate::smt::db::schema::COLUMNS;
 use crate::smt::db::transaction::RocksDBTransaction;
-use rocksdb::ops::{GetColumnFamilys, OpenCF};
+use rocksdb::ops::{CompactRangeCF, GetColumnFamilys, OpenCF};
 use rocksdb::{
     ColumnFamily, ColumnFamilyDescriptor, OptimisticTransactionDB, OptimisticTransactionOptions,
     Options, WriteOptions,
 };
+use std::env;
 use std::sync::Arc;
 
 #[derive(Clone)]
@@ -16,7 +17,8 @@ pub struct RocksDB {
 
 impl RocksDB {
     pub fn default() -> Result<Self, Error> {
-        Self::new_with_path("./store.db")
+        let path = env::var("ROCKSDB_PATH").unwrap_or_else(|_| "./store.db".to_owned());
+        Self::new_with_path(&path)
     }
 
     pub fn new_with_path(path: &str) -> Result<Self, Error> {
@@ -46,6 +48,17 @@ impl RocksDB {
             inner: self.inner.transaction(&write_options, &transaction_options),
         }
     }
+
+    /// Compact the whole range of all the columns
+    pub fn compact(&self) -> Result<(), Error> {
+        for col in 0..COLUMNS as Col {
+            let cf = cf_handle(&self.inner, col)?;
+            self.inner
+                .compact_range_cf(cf, None, None)
+                .map_err(|e| Error::RocksDBError(format!("compact_range_cf: {}", e)))?;
+        }
+        Ok(())
+    }
 }
 
 #[inline]
@@ -1,8 +1,8 @@
 use crate::error::Error;
 use crate::smt::db::db::cf_handle;
 use crate::smt::db::schema::Col;
-use rocksdb::ops::{DeleteCF, GetCF, PutCF};
-use rocksdb::{DBVector, OptimisticTransaction, OptimisticTransactionDB};
+use rocksdb::ops::{DeleteCF, GetCF, IterateCF, PutCF};
+use rocksdb::{DBIterator, DBVector, IteratorMode, OptimisticTransaction, OptimisticTransactionDB};
 use std::sync::Arc;
 
 pub struct RocksDBTransaction {
@@ -32,6 +32,13 @@ impl RocksDBTransaction {
             .map_err(|_e| Error::RocksDBError("transaction delete_cf".to_owned()))
     }
 
+    pub fn iter(&self, col: Col, mode: IteratorMode) -> Result<DBIterator, Error> {
+        let cf = cf_handle(&self.db, col)?;
+        self.inner
+            .iterator_cf(cf, mode)
+            .map_err(|_e| Error::RocksDBError("transaction iterator_cf".to_owned()))
+    }
+
     pub fn commit(&self) -> Result<(), Error> {
         self.inner
             .commit()
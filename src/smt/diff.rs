@@ -0,0 +1,274 @@
+use crate::db::{get_registered_ccids, get_registered_lock_hashes_and_ccids};
+use crate::error::Error;
+use crate::smt::smt::{decode_leaf_ccid, generate_history_leaf, init_smt, CotaSMT};
+use crate::smt::transaction::store_transaction::StoreTransaction;
+use crate::ROCKS_DB;
+use cota_smt::smt::H256;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The differences of the RocksDB SMT leaves from the leaves expected from MySQL
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LeafDiff {
+    /// The expected leaves which are absent in RocksDB
+    pub additions: Vec<(H256, H256)>,
+    /// The RocksDB leaves which are not registered in MySQL
+    pub removals:  Vec<(H256, H256)>,
+    /// The leaves with different values as (key, RocksDB value, expected value)
+    pub changes:   Vec<(H256, H256, H256)>,
+}
+
+impl LeafDiff {
+    pub fn is_empty(&self) -> bool {
+        self.additions.is_empty() && self.removals.is_empty() && self.changes.is_empty()
+    }
+
+    /// The leaves to update the RocksDB SMT to the expected one, the removed leaves are zero
+    pub fn updated_leaves(&self) -> Vec<(H256, H256)> {
+        self.additions
+            .iter()
+            .copied()
+            .chain(self.changes.iter().map(|(key, _, value)| (*key, *value)))
+            .chain(self.removals.iter().map(|(key, _)| (*key, H256::zero())))
+            .collect()
+    }
+
+    /// The counts and at most `limit` leaves of every kind of differences
+    pub fn to_json(&self, limit: usize) -> Value {
+        let leaves_json = |leaves: &[(H256, H256)]| -> Vec<Value> {
+            leaves
+                .iter()
+                .take(limit)
+                .map(|(key, value)| {
+                    json!({
+                        "lock_hash": hex::encode(key.as_slice()),
+                        "state": hex::encode(value.as_slice()),
+                    })
+                })
+                .collect()
+        };
+        let changes: Vec<Value> = self
+            .changes
+            .iter()
+            .take(limit)
+            .map(|(key, local_value, expected_value)| {
+                json!({
+                    "lock_hash": hex::encode(key.as_slice()),
+                    "local_state": hex::encode(local_value.as_slice()),
+                    "expected_state": hex::encode(expected_value.as_slice()),
+                })
+            })
+            .collect();
+        json!({
+            "additions_count": self.additions.len(),
+            "removals_count": self.removals.len(),
+            "changes_count": self.changes.len(),
+            "additions": leaves_json(&self.additions),
+            "removals": leaves_json(&self.removals),
+            "changes": changes,
+        })
+    }
+}
+
+pub struct SMTComparison {
+    pub mysql_root:     H256,
+    pub local_root:     H256,
+    pub pending_leaves: usize,
+    pub diff:           LeafDiff,
+}
+
+/// Compare the RocksDB SMT with the leaves of MySQL and the root of their SMT
+pub fn compare_smt_with_mysql<'a>(
+    smt: &CotaSMT<'a>,
+    mysql_root: H256,
+    mysql_leaves: Vec<(H256, H256)>,
+) -> Result<SMTComparison, Error> {
+    let pending_keys = get_pending_keys(smt)?;
+    Ok(SMTComparison {
+        mysql_root,
+        local_root: *smt.root(),
+        pending_leaves: pending_keys.len(),
+        diff: diff_smt_leaves(smt, mysql_leaves, &pending_keys)?,
+    })
+}
+
+/// The leaf of a lock hash expected from MySQL and the one in RocksDB, None if it is absent
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LeafState {
+    pub lock_hash: H256,
+    pub expected:  Option<H256>,
+    pub local:     Option<H256>,
+    pub pending:   bool,
+}
+
+impl LeafState {
+    /// The pending leaf may be ahead of MySQL until the syncer indexes the latest registration
+    pub fn is_consistent(&self) -> bool {
+        self.pending || self.expected == self.local
+    }
+
+    pub fn to_json(&self) -> Value {
+        let state_json = |state: Option<H256>| state.map(|state| hex::encode(state.as_slice()));
+        json!({
+            "lock_hash": hex::encode(self.lock_hash.as_slice()),
+            "registered": self.expected.is_some(),
+            "cota_cell_id": self.expected.as_ref().and_then(decode_leaf_ccid),
+            "expected_state": state_json(self.expected),
+            "local_state": state_json(self.local),
+            "pending": self.pending,
+            "consistent": self.is_consistent(),
+        })
+    }
+}
+
+/// Look up the leaves of the lock hashes in MySQL and RocksDB
+pub fn get_leaf_states(lock_hashes: Vec<[u8; 32]>) -> Result<Vec<LeafState>, Error> {
+    let expected_leaves: BTreeMap<H256, H256> = get_registered_ccids(lock_hashes.clone())?
+        .into_iter()
+        .map(generate_history_leaf)
+        .collect();
+    let transaction = &StoreTransaction::new((&ROCKS_DB).transaction());
+    let smt = init_smt(transaction)?;
+    let pending_keys = get_pending_keys(&smt)?;
+    lock_hashes
+        .into_iter()
+        .map(|lock_hash| {
+            let key = H256::from(lock_hash);
+            let local = smt.get(&key).map_err(|e| Error::SMTError(e.to_string()))?;
+            Ok(LeafState {
+                lock_hash: key,
+                expected:  expected_leaves.get(&key).copied(),
+                local:     Some(local).filter(|value| !value.is_zero()),
+                pending:   pending_keys.contains(&key),
+            })
+        })
+        .collect()
+}
+
+/// The SMT leaves expected from all the registrations in MySQL
+pub fn get_mysql_leaves() -> Result<Vec<(H256, H256)>, Error> {
+    Ok(get_registered_lock_hashes_and_ccids(None)?
+        .into_iter()
+        .map(generate_history_leaf)
+        .collect())
+}
+
+/// The keys of the temp leaves, which belong to the latest registration pending until the syncer
+/// indexes it
+pub fn get_pending_keys(smt: &CotaSMT) -> Result<BTreeSet<H256>, Error> {
+    Ok(smt
+        .store()
+        .get_leaves()?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect())
+}
+
+/// Compare the RocksDB SMT leaves with the expected leaves except the skipped keys
+pub fn diff_smt_leaves(
+    smt: &CotaSMT,
+    expected_leaves: Vec<(H256, H256)>,
+    skipped_keys: &BTreeSet<H256>,
+) -> Result<LeafDiff, Error> {
+    let local_leaves = smt
+        .store()
+        .get_all_leaves()?
+        .into_iter()
+        .filter(|(key, _)| !skipped_keys.contains(key))
+        .collect();
+    let expected_leaves = expected_leaves
+        .into_iter()
+        .filter(|(key, _)| !skipped_keys.contains(key))
+        .collect();
+    Ok(diff_leaves(local_leaves, expected_leaves))
+}
+
+pub fn diff_leaves(
+    local_leaves: Vec<(H256, H256)>,
+    expected_leaves: Vec<(H256, H256)>,
+) -> LeafDiff {
+    let mut local_leaves: BTreeMap<H256, H256> = local_leaves.into_iter().collect();
+    let mut diff = LeafDiff::default();
+    let mut expected_leaves = expected_leaves;
+    expected_leaves.sort_unstable_by_key(|(key, _)| *key);
+    for (key, expected_value) in expected_leaves {
+        match local_leaves.remove(&key) {
+            None => diff.additions.push((key, expected_value)),
+            Some(local_value) if local_value != expected_value => {
+                diff.changes.push((key, local_value, expected_value))
+            }
+            Some(_) => {}
+        }
+    }
+    diff.removals = local_leaves.into_iter().collect();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_leaves() {
+        let registered = H256::from([255u8; 32]);
+        let mut ccid_value = [255u8; 32];
+        ccid_value[0..8].copy_from_slice(&8u64.to_be_bytes());
+        let ccid_value = H256::from(ccid_value);
+
+        let local_leaves = vec![
+            (H256::from([1u8; 32]), registered),
+            (H256::from([2u8; 32]), registered),
+            (H256::from([3u8; 32]), registered),
+        ];
+        let expected_leaves = vec![
+            (H256::from([4u8; 32]), registered),
+            (H256::from([2u8; 32]), ccid_value),
+            (H256::from([1u8; 32]), registered),
+        ];
+        let diff = diff_leaves(local_leaves.clone(), expected_leaves);
+        assert_eq!(diff, LeafDiff {
+            additions: vec![(H256::from([4u8; 32]), registered)],
+            removals:  vec![(H256::from([3u8; 32]), registered)],
+            changes:   vec![(H256::from([2u8; 32]), registered, ccid_value)],
+        });
+        assert_eq!(diff.updated_leaves(), vec![
+            (H256::from([4u8; 32]), registered),
+            (H256::from([2u8; 32]), ccid_value),
+            (H256::from([3u8; 32]), H256::zero()),
+        ]);
+        assert!(diff_leaves(local_leaves.clone(), local_leaves).is_empty());
+    }
+
+    #[test]
+    fn test_leaf_state() {
+        let (key, ccid_value) = generate_history_leaf((H256::from([1u8; 32]), 5));
+        let registered = H256::from([255u8; 32]);
+        let leaf = LeafState {
+            lock_hash: key,
+            expected:  Some(ccid_value),
+            local:     Some(registered),
+            pending:   false,
+        };
+        assert!(!leaf.is_consistent());
+        assert_eq!(
+            leaf.to_json(),
+            json!({
+                "lock_hash": hex::encode([1u8; 32]),
+                "registered": true,
+                "cota_cell_id": 5,
+                "expected_state": hex::encode(ccid_value.as_slice()),
+                "local_state": hex::encode([255u8; 32]),
+                "pending": false,
+                "consistent": false,
+            })
+        );
+        let pending_leaf = LeafState {
+            expected: None,
+            pending: true,
+            ..leaf
+        };
+        assert!(pending_leaf.is_consistent());
+        assert_eq!(pending_leaf.to_json()["cota_cell_id"], json!(null));
+    }
+}
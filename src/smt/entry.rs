@@ -1,7 +1,15 @@
+use crate::blocking::{run_blocking, BlockingPool};
 use crate::db::check_lock_hashes_registered;
 use crate::error::Error;
-use crate::indexer::index::{get_registry_info, RegistryInfo};
-use crate::smt::smt::{generate_history_smt, init_smt, Extension};
+use crate::indexer::cache::{get_cached_registry_cell, refresh_registry_cell};
+use crate::smt::diff::{
+    compare_smt_with_mysql, diff_smt_leaves, get_mysql_leaves, get_pending_keys, LeafDiff,
+    SMTComparison,
+};
+use crate::smt::history::HistorySMT;
+use crate::smt::smt::{
+    generate_history_leaf, generate_history_smt, init_smt, reset_smt_temp_leaves, Extension,
+};
 use crate::smt::transaction::store_transaction::StoreTransaction;
 use crate::ROCKS_DB;
 use cota_smt::common::{Byte32, BytesBuilder};
@@ -13,34 +21,63 @@ use cota_smt::smt::H256;
 use lazy_static::lazy_static;
 use log::info;
 use parking_lot::{Condvar, Mutex};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
 use std::sync::Arc;
+use tokio::runtime::Handle;
 
 lazy_static! {
     static ref SMT_LOCK: Arc<(Mutex<bool>, Condvar)> =
         Arc::new((Mutex::new(false), Condvar::new()));
 }
 
-pub async fn generate_registry_smt(lock_hashes: Vec<[u8; 32]>) -> Result<(String, String), Error> {
-    let update_leaves_count = lock_hashes.len();
-    let registry_state = check_lock_hashes_registered(lock_hashes.clone())?.0;
+/// Register the lock hashes with their ccids, and the leaf value of the unknown ccid `u64::MAX`
+/// is `[255u8; 32]`
+pub async fn generate_registry_smt(
+    registries: Vec<([u8; 32], u64)>,
+) -> Result<(String, String), Error> {
+    let update_leaves_count = registries.len();
+    let checked_lock_hashes = registries.iter().map(|registry| registry.0).collect();
+    let registry_state = run_blocking(BlockingPool::Query, move || {
+        check_lock_hashes_registered(checked_lock_hashes)
+    })
+    .await?
+    .0;
     if registry_state {
         return Err(Error::LockHashHasRegistered);
     }
     let mut update_leaves: Vec<(H256, H256)> = Vec::with_capacity(update_leaves_count);
     let mut previous_leaves: Vec<(H256, H256)> = Vec::with_capacity(update_leaves_count);
-    let registry_value = [255u8; 32];
-    let RegistryInfo { smt_root } = get_registry_info().await?;
-    for lock_hash in lock_hashes.into_iter() {
-        let key: H256 = H256::from(lock_hash);
-        let value: H256 = H256::from(registry_value);
+    let smt_root = get_cached_registry_cell().await?.info.smt_root;
+    for (lock_hash, ccid) in registries.into_iter() {
+        let (key, value) = generate_history_leaf((H256::from(lock_hash), ccid));
         update_leaves.push((key, value));
         previous_leaves.push((key, H256::zero()));
     }
 
+    let handle = Handle::current();
+    run_blocking(BlockingPool::Register, move || {
+        update_registry_smt(update_leaves, previous_leaves, smt_root, handle)
+    })
+    .await
+}
+
+fn update_registry_smt(
+    update_leaves: Vec<(H256, H256)>,
+    previous_leaves: Vec<(H256, H256)>,
+    smt_root: [u8; 32],
+    handle: Handle,
+) -> Result<(String, String), Error> {
     let transaction = &StoreTransaction::new((&ROCKS_DB).transaction());
     let mut smt = init_smt(transaction)?;
 
     with_lock(|| {
+        let mut smt_root = smt_root;
+        if smt.root().as_slice() != smt_root.as_slice() {
+            // The cached registry cell may be stale, and a stale root would revert the pending
+            // leaves, so the live one is fetched before they are reverted
+            smt_root = handle.block_on(refresh_registry_cell())?.info.smt_root;
+        }
         generate_history_smt(&mut smt, smt_root)?;
         info!(
             "registry cell smt root: {:?}",
@@ -64,7 +101,156 @@ pub async fn generate_registry_smt(lock_hashes: Vec<[u8; 32]>) -> Result<(String
 
     let merkel_proof_vec: Vec<u8> = registry_merkle_proof_compiled.into();
 
-    let registry_vec = update_leaves
+    let registry_entry = encode_registry_entry(&update_leaves, merkel_proof_vec);
+
+    Ok((root_hash, registry_entry))
+}
+
+/// Rebuild the SMT of MySQL in memory and compare it with the RocksDB SMT, which is read under
+/// the lock so that no registration changes it in the middle of the comparison
+pub fn compare_registry_smt() -> Result<SMTComparison, Error> {
+    let mysql_leaves = get_mysql_leaves()?;
+    let mut mysql_smt = HistorySMT::default();
+    mysql_smt
+        .update_all(mysql_leaves.clone())
+        .map_err(|e| Error::SMTError(e.to_string()))?;
+    let mut comparison = None;
+
+    with_lock(|| {
+        let transaction = &StoreTransaction::new((&ROCKS_DB).transaction());
+        let smt = init_smt(transaction)?;
+        comparison = Some(compare_smt_with_mysql(
+            &smt,
+            *mysql_smt.root(),
+            mysql_leaves.clone(),
+        )?);
+        Ok(())
+    })?;
+
+    Ok(comparison.expect("Registry comparison should exist"))
+}
+
+/// Reconcile the RocksDB SMT with MySQL by adding the missing leaves, removing the unregistered
+/// leaves and fixing the leaves whose ccids have changed. The leaves of the pending registration
+/// are kept, and nothing is written if it is a dry run.
+pub fn reconcile_registry_smt(dry_run: bool) -> Result<ReconcileReport, Error> {
+    let transaction = &StoreTransaction::new((&ROCKS_DB).transaction());
+    let mut smt = init_smt(transaction)?;
+    let mut report = None;
+
+    with_lock(|| {
+        let old_root = *smt.root();
+        let pending_keys = get_pending_keys(&smt)?;
+        let diff = diff_smt_leaves(&smt, get_mysql_leaves()?, &pending_keys)?;
+        if !dry_run && !diff.is_empty() {
+            smt.update_all(diff.updated_leaves())
+                .map_err(|e| Error::SMTError(e.to_string()))?;
+            smt.store()
+                .save_root(smt.root())
+                .map_err(|e| Error::SMTError(e.to_string()))?;
+            transaction.commit()?;
+        }
+        info!(
+            "Reconcile registry smt: {} additions, {} removals and {} changes",
+            diff.additions.len(),
+            diff.removals.len(),
+            diff.changes.len()
+        );
+        report = Some(ReconcileReport {
+            old_root,
+            new_root: *smt.root(),
+            diff,
+        });
+        Ok(())
+    })?;
+
+    Ok(report.expect("Reconcile report should exist"))
+}
+
+/// Rebuild the RocksDB SMT from MySQL, the pending registration is reverted and all the leaves
+/// are compared with MySQL.
+pub fn resync_registry_smt() -> Result<ReconcileReport, Error> {
+    let transaction = &StoreTransaction::new((&ROCKS_DB).transaction());
+    let mut smt = init_smt(transaction)?;
+    let mut report = None;
+
+    with_lock(|| {
+        let old_root = *smt.root();
+        reset_smt_temp_leaves(&mut smt)?;
+        let diff = diff_smt_leaves(&smt, get_mysql_leaves()?, &BTreeSet::new())?;
+        smt.update_all(diff.updated_leaves())
+            .map_err(|e| Error::SMTError(e.to_string()))?;
+        smt.store()
+            .save_root(smt.root())
+            .map_err(|e| Error::SMTError(e.to_string()))?;
+        smt.store().remove_leaves()?;
+        transaction.commit()?;
+        info!(
+            "Resync registry smt: {} additions, {} removals and {} changes",
+            diff.additions.len(),
+            diff.removals.len(),
+            diff.changes.len()
+        );
+        report = Some(ReconcileReport {
+            old_root,
+            new_root: *smt.root(),
+            diff,
+        });
+        Ok(())
+    })?;
+
+    Ok(report.expect("Resync report should exist"))
+}
+
+/// Revert the leaves of the pending registration to their previous values and drop them, and
+/// return the old and new SMT roots and the number of the dropped leaves.
+pub fn drop_pending_leaves() -> Result<(H256, H256, usize), Error> {
+    let transaction = &StoreTransaction::new((&ROCKS_DB).transaction());
+    let mut smt = init_smt(transaction)?;
+    let mut old_root = H256::zero();
+    let mut dropped = 0;
+
+    with_lock(|| {
+        old_root = *smt.root();
+        let pending_leaves = smt.store().get_leaves()?.unwrap_or_default();
+        dropped = pending_leaves.len();
+        if dropped == 0 {
+            return Ok(());
+        }
+        smt.update_all(pending_leaves)
+            .map_err(|e| Error::SMTError(e.to_string()))?;
+        smt.store()
+            .save_root(smt.root())
+            .map_err(|e| Error::SMTError(e.to_string()))?;
+        smt.store().remove_leaves()?;
+        transaction.commit()
+    })?;
+    info!("Drop {} pending leaves of registry smt", dropped);
+
+    Ok((old_root, *smt.root(), dropped))
+}
+
+pub struct ReconcileReport {
+    pub old_root: H256,
+    pub new_root: H256,
+    pub diff:     LeafDiff,
+}
+
+impl ReconcileReport {
+    pub fn to_json(&self, dry_run: bool) -> Value {
+        json!({
+            "dry_run": dry_run,
+            "old_smt_root_hash": hex::encode(self.old_root.as_slice()),
+            "new_smt_root_hash": hex::encode(self.new_root.as_slice()),
+            "diff": self.diff.to_json(usize::MAX),
+        })
+    }
+}
+
+/// Encode the registry leaves and the compiled merkle proof into the hex of
+/// `CotaNFTRegistryEntries`
+pub fn encode_registry_entry(leaves: &[(H256, H256)], merkel_proof_vec: Vec<u8>) -> String {
+    let registry_vec = leaves
         .iter()
         .map(|leave| {
             let key: [u8; 32] = leave.0.into();
@@ -86,9 +272,7 @@ pub async fn generate_registry_smt(lock_hashes: Vec<[u8; 32]>) -> Result<(String
         .proof(merkel_proof_bytes)
         .build();
 
-    let registry_entry = hex::encode(registry_entries.as_slice());
-
-    Ok((root_hash, registry_entry))
+    hex::encode(registry_entries.as_slice())
 }
 
 fn with_lock<F>(mut operator: F) -> Result<(), Error>
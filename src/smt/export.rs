@@ -0,0 +1,229 @@
+use crate::error::Error;
+use crate::smt::db::schema::COLUMN_SMT_LEAF;
+use crate::smt::smt::decode_leaf_ccid;
+use crate::smt::transaction::store_transaction::{KeyValue, StoreTransaction};
+use crate::ROCKS_DB;
+use cota_smt::smt::H256;
+use serde_json::json;
+use std::convert::TryInto;
+use std::io::{BufWriter, Write};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExportFormat {
+    JsonLines,
+    Csv,
+}
+
+impl FromStr for ExportFormat {
+    type Err = Error;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "jsonl" => Ok(ExportFormat::JsonLines),
+            "csv" => Ok(ExportFormat::Csv),
+            _ => Err(Error::RequestParamsInvalid(format!(
+                "Unknown export format '{}'",
+                format
+            ))),
+        }
+    }
+}
+
+/// The lock hashes to export, either starting with the prefix or in the range [start, end)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ExportRange {
+    Prefix(Vec<u8>),
+    Range(Vec<u8>, Vec<u8>),
+}
+
+impl FromStr for ExportRange {
+    type Err = Error;
+
+    /// Parse the hex prefix, or the hex start and end separated by `..`
+    fn from_str(range: &str) -> Result<Self, Self::Err> {
+        let parse_hex = |value: &str| {
+            hex::decode(value.strip_prefix("0x").unwrap_or(value)).map_err(|_e| {
+                Error::RequestParamsInvalid(format!("Export range '{}' is not hex string", range))
+            })
+        };
+        match range.split_once("..") {
+            None => Ok(ExportRange::Prefix(parse_hex(range)?)),
+            Some((_, "")) => Err(Error::RequestParamsInvalid(format!(
+                "Export range '{}' has no end",
+                range
+            ))),
+            Some((start, end)) => Ok(ExportRange::Range(parse_hex(start)?, parse_hex(end)?)),
+        }
+    }
+}
+
+/// Write the SMT leaves with the lock hashes in the range, one leaf per line, and return the
+/// number of the exported leaves.
+pub fn export_leaves<W: Write>(
+    transaction: &StoreTransaction,
+    writer: &mut W,
+    format: ExportFormat,
+    range: &ExportRange,
+) -> Result<u64, Error> {
+    let write_error = |e: std::io::Error| Error::RocksDBError(format!("Export write error: {}", e));
+    if format == ExportFormat::Csv {
+        writeln!(writer, "lock_hash,state,cota_cell_id").map_err(write_error)?;
+    }
+    let leaves: Box<dyn Iterator<Item = KeyValue> + '_> = match range {
+        ExportRange::Prefix(prefix) => Box::new(transaction.prefix_iter(COLUMN_SMT_LEAF, prefix)?),
+        ExportRange::Range(start, end) => {
+            Box::new(transaction.range_iter(COLUMN_SMT_LEAF, start, end)?)
+        }
+    };
+    let mut count = 0;
+    for (key, value) in leaves {
+        let value: [u8; 32] = value
+            .as_ref()
+            .try_into()
+            .map_err(|_e| Error::SMTError("SMT leaf value parse error".to_owned()))?;
+        let line = format_leaf(format, &key, &H256::from(value));
+        writeln!(writer, "{}", line).map_err(write_error)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Export the leaves of the registry SMT in RocksDB to stdout
+pub fn export_registry_leaves(format: ExportFormat, range: &ExportRange) -> Result<u64, Error> {
+    let transaction = StoreTransaction::new(ROCKS_DB.transaction());
+    let stdout = std::io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    let count = export_leaves(&transaction, &mut writer, format, range)?;
+    writer
+        .flush()
+        .map_err(|e| Error::RocksDBError(format!("Export write error: {}", e)))?;
+    Ok(count)
+}
+
+fn format_leaf(format: ExportFormat, key: &[u8], value: &H256) -> String {
+    let lock_hash = hex::encode(key);
+    let state = hex::encode(value.as_slice());
+    let ccid = decode_leaf_ccid(value);
+    match format {
+        ExportFormat::JsonLines => json!({
+            "lock_hash": lock_hash,
+            "state": state,
+            "cota_cell_id": ccid,
+        })
+        .to_string(),
+        ExportFormat::Csv => format!(
+            "{},{},{}",
+            lock_hash,
+            state,
+            ccid.map_or(String::new(), |ccid| ccid.to_string())
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::smt::db::db::RocksDB;
+
+    #[test]
+    fn test_export_leaves() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = RocksDB::new_with_path(temp_dir.path().to_str().unwrap()).unwrap();
+        let transaction = StoreTransaction::new(db.transaction());
+        let mut ccid_value = [255u8; 32];
+        ccid_value[0..8].copy_from_slice(&8u64.to_be_bytes());
+        let leaves = [
+            ([0x11u8; 32], [255u8; 32]),
+            ([0x12u8; 32], ccid_value),
+            ([0x21u8; 32], [255u8; 32]),
+        ];
+        for (key, value) in leaves.iter() {
+            transaction.insert_raw(COLUMN_SMT_LEAF, key, value).unwrap();
+        }
+
+        let mut output = Vec::new();
+        let prefix = ExportRange::Prefix(vec![0x12u8]);
+        let count = export_leaves(&transaction, &mut output, ExportFormat::Csv, &prefix).unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!(
+                "lock_hash,state,cota_cell_id\n{},{},8\n",
+                hex::encode([0x12u8; 32]),
+                hex::encode(ccid_value)
+            )
+        );
+
+        let mut output = Vec::new();
+        let all = ExportRange::Prefix(vec![]);
+        let count =
+            export_leaves(&transaction, &mut output, ExportFormat::JsonLines, &all).unwrap();
+        assert_eq!(count, 3);
+        let lines: Vec<serde_json::Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines[0],
+            json!({
+                "lock_hash": hex::encode([0x11u8; 32]),
+                "state": hex::encode([255u8; 32]),
+                "cota_cell_id": null,
+            })
+        );
+        assert_eq!(lines[1]["cota_cell_id"], json!(8));
+
+        let mut output = Vec::new();
+        let range = ExportRange::Range(vec![0x12u8], vec![0x21u8]);
+        let count = export_leaves(&transaction, &mut output, ExportFormat::Csv, &range).unwrap();
+        assert_eq!(count, 1);
+        assert!(String::from_utf8(output).unwrap().ends_with(&format!(
+            "{},{},8\n",
+            hex::encode([0x12u8; 32]),
+            hex::encode(ccid_value)
+        )));
+    }
+
+    #[test]
+    fn test_parse_export_range() {
+        assert_eq!(
+            "0x1a2b".parse::<ExportRange>(),
+            Ok(ExportRange::Prefix(vec![0x1a, 0x2b]))
+        );
+        assert_eq!("".parse::<ExportRange>(), Ok(ExportRange::Prefix(vec![])));
+        assert_eq!(
+            "12..0x21".parse::<ExportRange>(),
+            Ok(ExportRange::Range(vec![0x12], vec![0x21]))
+        );
+        assert_eq!(
+            "..21".parse::<ExportRange>(),
+            Ok(ExportRange::Range(vec![], vec![0x21]))
+        );
+        assert_eq!(
+            "12..".parse::<ExportRange>(),
+            Err(Error::RequestParamsInvalid(
+                "Export range '12..' has no end".to_owned()
+            ))
+        );
+        assert_eq!(
+            "1g".parse::<ExportRange>(),
+            Err(Error::RequestParamsInvalid(
+                "Export range '1g' is not hex string".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_export_format() {
+        assert_eq!("jsonl".parse::<ExportFormat>(), Ok(ExportFormat::JsonLines));
+        assert_eq!("csv".parse::<ExportFormat>(), Ok(ExportFormat::Csv));
+        assert_eq!(
+            "xml".parse::<ExportFormat>(),
+            Err(Error::RequestParamsInvalid(
+                "Unknown export format 'xml'".to_owned()
+            ))
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use crate::db::{
+    get_registered_lock_hashes_and_ccids, get_registrations_version, get_syncer_tip_block_number,
+};
+use crate::error::Error;
+use crate::smt::smt::generate_history_leaf;
+use cota_smt::smt::{Blake2bHasher, H256};
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use sparse_merkle_tree::default_store::DefaultStore;
+use sparse_merkle_tree::SparseMerkleTree;
+use std::collections::VecDeque;
+
+pub type HistorySMT = SparseMerkleTree<Blake2bHasher, H256, DefaultStore<H256>>;
+
+// The count and the latest update time of the registrations no later than the block
+type RegistrationsVersion = (u64, Option<String>);
+
+const HISTORY_ROOT_CACHE_SIZE: usize = 128;
+
+lazy_static! {
+    // The roots and the leaves counts of the latest requested block numbers with the versions of
+    // their registrations, the recent one last
+    static ref HISTORY_ROOTS: Mutex<VecDeque<(u64, RegistrationsVersion, H256, usize)>> =
+        Mutex::new(VecDeque::with_capacity(HISTORY_ROOT_CACHE_SIZE));
+}
+
+/// Build the registry SMT in memory from the lock hashes registered no later than the block
+/// number, which must not be beyond the syncer tip.
+///
+/// The leaf values come from the current `cota_cell_id` of the registrations, as the database
+/// keeps no history of them, so the past roots are right as long as the ccids were set when the
+/// lock hashes were registered.
+pub fn generate_smt_at(block_number: u64) -> Result<(HistorySMT, usize), Error> {
+    let version = get_registrations_version(block_number)?;
+    build_smt_at(block_number, version)
+}
+
+fn build_smt_at(
+    block_number: u64,
+    version: RegistrationsVersion,
+) -> Result<(HistorySMT, usize), Error> {
+    let tip_block_number = get_syncer_tip_block_number()?;
+    if block_number > tip_block_number {
+        return Err(Error::RequestParamsInvalid(format!(
+            "Block number {} is beyond the syncer tip {}",
+            block_number, tip_block_number
+        )));
+    }
+    let leaves: Vec<(H256, H256)> = get_registered_lock_hashes_and_ccids(Some(block_number))?
+        .into_iter()
+        .map(generate_history_leaf)
+        .collect();
+    let leaves_count = leaves.len();
+    let mut smt = HistorySMT::default();
+    smt.update_all(leaves)
+        .map_err(|e| Error::SMTError(e.to_string()))?;
+    cache_root_at(block_number, version, *smt.root(), leaves_count);
+    Ok((smt, leaves_count))
+}
+
+/// Return the registry SMT root and the leaves count as of the block number, which are cached
+/// for the latest requested block numbers so that the SMT is not rebuilt for the same block.
+///
+/// The cached root is only reused while the version of the registrations no later than the
+/// block is unchanged, as the leaves take the latest ccids.
+pub fn get_root_at(block_number: u64) -> Result<(H256, usize), Error> {
+    let version = get_registrations_version(block_number)?;
+    let cached_root = {
+        let mut roots = HISTORY_ROOTS.lock();
+        let index = roots.iter().position(|(cached, cached_version, _, _)| {
+            *cached == block_number && *cached_version == version
+        });
+        let cached = index.and_then(|index| roots.remove(index));
+        // Move the hit to the back so that the least recently requested one is evicted first
+        cached.map(|cached| {
+            let root = (cached.2, cached.3);
+            roots.push_back(cached);
+            root
+        })
+    };
+    match cached_root {
+        Some(root) => Ok(root),
+        None => build_smt_at(block_number, version).map(|(smt, count)| (*smt.root(), count)),
+    }
+}
+
+fn cache_root_at(block_number: u64, version: RegistrationsVersion, root: H256, count: usize) {
+    let mut roots = HISTORY_ROOTS.lock();
+    roots.retain(|(cached, _, _, _)| *cached != block_number);
+    if roots.len() >= HISTORY_ROOT_CACHE_SIZE {
+        roots.pop_front();
+    }
+    roots.push_back((block_number, version, root, count));
+}
+
+/// Generate the compiled merkle proof of the lock hashes against the registry SMT as of the block
+/// number, the absent lock hashes have zero leaves so the proof proves their non-membership.
+///
+/// The SMT is too large to cache, so it is rebuilt by `generate_smt_at` which also caches its
+/// root for `get_root_at`.
+pub fn generate_proof_at(
+    block_number: u64,
+    lock_hashes: Vec<[u8; 32]>,
+) -> Result<(H256, Vec<(H256, H256)>, Vec<u8>), Error> {
+    let (smt, _) = generate_smt_at(block_number)?;
+    let keys: Vec<H256> = lock_hashes.into_iter().map(H256::from).collect();
+    let leaves = keys
+        .iter()
+        .map(|key| smt.get(key).map(|value| (*key, value)))
+        .collect::<Result<Vec<(H256, H256)>, _>>()
+        .map_err(|e| Error::SMTError(e.to_string()))?;
+    let proof = smt
+        .merkle_proof(keys)
+        .and_then(|proof| proof.compile(leaves.clone()))
+        .map_err(|e| Error::SMTError(e.to_string()))?;
+    Ok((*smt.root(), leaves, proof.into()))
+}
@@ -1,6 +1,11 @@
 pub mod db;
+pub mod diff;
 pub mod entry;
+pub mod export;
+pub mod history;
 mod smt;
+pub mod stats;
 mod store;
 pub mod transaction;
 mod types;
+pub mod verify;
@@ -1,21 +1,21 @@
-use crate::db::{check_lock_hashes_registered, get_registered_lock_hashes_and_ccids};
 use crate::error::Error;
+use crate::indexer::cache::invalidate_registry_cell;
 use crate::smt::db::schema::{
     COLUMN_SMT_BRANCH, COLUMN_SMT_LEAF, COLUMN_SMT_ROOT, COLUMN_SMT_TEMP_LEAVES,
 };
+use crate::smt::diff::{diff_smt_leaves, get_mysql_leaves};
 use crate::smt::store::smt_store::SMTStore;
 use crate::smt::transaction::store_transaction::StoreTransaction;
 use chrono::prelude::*;
 use cota_smt::smt::{Blake2bHasher, H256};
-use log::debug;
-use sparse_merkle_tree::traits::Store;
+use log::{debug, warn};
 use sparse_merkle_tree::SparseMerkleTree;
+use std::collections::BTreeSet;
 
 pub type CotaSMT<'a> = SparseMerkleTree<Blake2bHasher, H256, SMTStore<'a>>;
 
 pub trait Extension {
     fn save_root_and_leaves(&self, leaves: Vec<(H256, H256)>) -> Result<(), Error>;
-    fn is_non_existent(&self, leaf_key: &H256) -> bool;
 }
 
 impl<'a> Extension for CotaSMT<'a> {
@@ -27,13 +27,6 @@ impl<'a> Extension for CotaSMT<'a> {
         debug!("Save latest smt root: {:?} and leaves", self.root());
         Ok(())
     }
-
-    fn is_non_existent(&self, leaf_key: &H256) -> bool {
-        if let Ok(result) = self.store().get_leaf(leaf_key) {
-            return result.is_none();
-        }
-        true
-    }
 }
 
 pub fn init_smt<'a>(transaction: &'a StoreTransaction) -> Result<CotaSMT<'a>, Error> {
@@ -68,25 +61,31 @@ pub fn generate_history_smt<'a>(smt: &mut CotaSMT<'a>, smt_root: [u8; 32]) -> Re
             return Ok(());
         }
     }
+    // The registry cell may have been updated since it was cached
+    invalidate_registry_cell();
     generate_mysql_smt(smt)
 }
 
 fn generate_mysql_smt<'a>(smt: &mut CotaSMT<'a>) -> Result<(), Error> {
     let start_time = Local::now().timestamp_millis();
-    let registered_lock_hashes_and_ccids = get_registered_lock_hashes_and_ccids()?;
-    let is_smt_full_leaves = smt.root() == &H256::zero() || is_temp_leaves_non_exit(smt)?;
-    let leaves = if is_smt_full_leaves {
-        registered_lock_hashes_and_ccids
-            .into_iter()
-            .map(generate_history_leaf)
-            .collect()
+    let mysql_leaves = get_mysql_leaves()?;
+    let leaves = if smt.root() == &H256::zero() {
+        mysql_leaves
     } else {
+        // The temp leaves have been reset, so all the leaves are compared with MySQL. Only the
+        // missing leaves are added on the registration path, and the removals and the ccid
+        // changes are left to the reconciliation and the admin resync.
         debug!("Compare rocksdb and mysql to get diff leaves");
-        registered_lock_hashes_and_ccids
-            .into_iter()
-            .filter(|registry| smt.is_non_existent(&registry.0))
-            .map(generate_history_leaf)
-            .collect()
+        let diff = diff_smt_leaves(smt, mysql_leaves, &BTreeSet::new())?;
+        debug!("Diff leaves: {} additions", diff.additions.len());
+        if !diff.removals.is_empty() || !diff.changes.is_empty() {
+            warn!(
+                "Diff leaves: {} removals and {} changes are left to the reconciliation",
+                diff.removals.len(),
+                diff.changes.len()
+            );
+        }
+        diff.additions
     };
     smt.update_all(leaves).expect("SMT update leave error");
     let diff_time = (Local::now().timestamp_millis() - start_time) as f64 / 1000f64;
@@ -94,7 +93,7 @@ fn generate_mysql_smt<'a>(smt: &mut CotaSMT<'a>) -> Result<(), Error> {
     Ok(())
 }
 
-fn reset_smt_temp_leaves<'a>(smt: &mut CotaSMT<'a>) -> Result<(), Error> {
+pub fn reset_smt_temp_leaves<'a>(smt: &mut CotaSMT<'a>) -> Result<(), Error> {
     let leaves_opt = smt.store().get_leaves()?;
     if let Some(leaves) = leaves_opt {
         smt.update_all(leaves)
@@ -104,17 +103,7 @@ fn reset_smt_temp_leaves<'a>(smt: &mut CotaSMT<'a>) -> Result<(), Error> {
     Ok(())
 }
 
-fn is_temp_leaves_non_exit<'a>(smt: &mut CotaSMT<'a>) -> Result<bool, Error> {
-    let leaves_opt = smt.store().get_leaves()?;
-    if let Some(leaves) = leaves_opt {
-        let lock_hashes: Vec<[u8; 32]> = leaves.into_iter().map(|leaf| leaf.0.into()).collect();
-        let is_non_exist = !check_lock_hashes_registered(lock_hashes)?.0;
-        return Ok(is_non_exist);
-    }
-    Ok(true)
-}
-
-fn generate_history_leaf(registry: (H256, u64)) -> (H256, H256) {
+pub fn generate_history_leaf(registry: (H256, u64)) -> (H256, H256) {
     let (key, ccid) = registry;
     let mut value = [0xFFu8; 32];
     if ccid != u64::MAX {
@@ -122,3 +111,14 @@ fn generate_history_leaf(registry: (H256, u64)) -> (H256, H256) {
     }
     (key, H256::from(value))
 }
+
+/// Decode the ccid from the leaf value of `generate_history_leaf`, None if the ccid is unknown
+pub fn decode_leaf_ccid(value: &H256) -> Option<u64> {
+    let value = value.as_slice();
+    if value[8..].iter().any(|byte| *byte != 0xFF) {
+        return None;
+    }
+    let mut ccid = [0u8; 8];
+    ccid.copy_from_slice(&value[0..8]);
+    Some(u64::from_be_bytes(ccid)).filter(|ccid| *ccid != u64::MAX)
+}
@@ -0,0 +1,28 @@
+use crate::error::Error;
+use crate::smt::db::schema::COLUMN_SMT_LEAF;
+use crate::smt::smt::init_smt;
+use crate::smt::transaction::store_transaction::StoreTransaction;
+use crate::ROCKS_DB;
+use cota_smt::smt::H256;
+
+pub struct SMTStats {
+    pub root:              H256,
+    pub leaves_count:      u64,
+    pub temp_leaves_count: u64,
+}
+
+/// The temp leaves are the previous leaves of the latest registration which are pending until
+/// the registry cell is updated on chain.
+pub fn get_smt_stats() -> Result<SMTStats, Error> {
+    let transaction = &StoreTransaction::new((&ROCKS_DB).transaction());
+    let smt = init_smt(transaction)?;
+    let temp_leaves_count = smt
+        .store()
+        .get_leaves()?
+        .map_or(0, |leaves| leaves.len() as u64);
+    Ok(SMTStats {
+        root: *smt.root(),
+        leaves_count: transaction.count(COLUMN_SMT_LEAF)?,
+        temp_leaves_count,
+    })
+}
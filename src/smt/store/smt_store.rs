@@ -104,6 +104,28 @@ impl<'a> SMTStore<'a> {
             None => Ok(None),
         }
     }
+
+    pub fn remove_leaves(&self) -> Result<(), Error> {
+        self.store.delete(self.leaves_col, &REGISTRY_KEY)
+    }
+
+    /// Load all the leaves of the SMT from the leaf column
+    pub fn get_all_leaves(&self) -> Result<Vec<(H256, H256)>, Error> {
+        self.store
+            .iter(self.leaf_col)?
+            .map(|(key, value)| {
+                let key: [u8; 32] = key
+                    .as_ref()
+                    .try_into()
+                    .map_err(|_e| Error::SMTError("SMT leaf key parse error".to_owned()))?;
+                let value: [u8; 32] = value
+                    .as_ref()
+                    .try_into()
+                    .map_err(|_e| Error::SMTError("SMT leaf value parse error".to_owned()))?;
+                Ok((key.into(), value.into()))
+            })
+            .collect()
+    }
 }
 
 impl<'a> Store<H256> for SMTStore<'a> {
@@ -1,6 +1,9 @@
 use crate::error::Error;
 use crate::smt::db::schema::Col;
 use crate::smt::db::transaction::RocksDBTransaction;
+use rocksdb::{DBIterator, Direction, IteratorMode};
+
+pub type KeyValue = (Box<[u8]>, Box<[u8]>);
 
 pub struct StoreTransaction {
     pub(crate) inner: RocksDBTransaction,
@@ -26,6 +29,39 @@ impl StoreTransaction {
         self.inner.delete(col, key)
     }
 
+    pub fn iter(&self, col: Col) -> Result<DBIterator, Error> {
+        self.inner.iter(col, IteratorMode::Start)
+    }
+
+    /// Iterate the keys starting with the prefix in order
+    pub fn prefix_iter<'a>(
+        &'a self,
+        col: Col,
+        prefix: &'a [u8],
+    ) -> Result<impl Iterator<Item = KeyValue> + 'a, Error> {
+        Ok(self
+            .inner
+            .iter(col, IteratorMode::From(prefix, Direction::Forward))?
+            .take_while(move |(key, _)| key.starts_with(prefix)))
+    }
+
+    /// Iterate the keys in the range [start, end) in order
+    pub fn range_iter<'a>(
+        &'a self,
+        col: Col,
+        start: &'a [u8],
+        end: &'a [u8],
+    ) -> Result<impl Iterator<Item = KeyValue> + 'a, Error> {
+        Ok(self
+            .inner
+            .iter(col, IteratorMode::From(start, Direction::Forward))?
+            .take_while(move |(key, _)| key.as_ref() < end))
+    }
+
+    pub fn count(&self, col: Col) -> Result<u64, Error> {
+        Ok(self.iter(col)?.count() as u64)
+    }
+
     pub fn commit(&self) -> Result<(), Error> {
         self.inner.commit()
     }
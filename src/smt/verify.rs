@@ -0,0 +1,233 @@
+use crate::error::Error;
+use cota_smt::molecule::prelude::*;
+use cota_smt::registry::CotaNFTRegistryEntries;
+use cota_smt::smt::{Blake2bHasher, H256};
+use serde_json::{json, Map, Value};
+use sparse_merkle_tree::CompiledMerkleProof;
+use std::convert::TryInto;
+
+/// The decoded `CotaNFTRegistryEntries` which is returned as `registry_smt_entry` by
+/// `register_cota_cells`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RegistryEntry {
+    pub registries: Vec<(H256, H256)>,
+    pub proof:      Vec<u8>,
+}
+
+impl RegistryEntry {
+    pub fn from_hex(entry: &str) -> Result<Self, Error> {
+        let entry_bytes = decode_hex(entry, "Registry entry")?;
+        let registry_entries = CotaNFTRegistryEntries::from_slice(&entry_bytes).map_err(|_e| {
+            Error::RegistryEntryError("Registry entry is not CotaNFTRegistryEntries".to_owned())
+        })?;
+        let registry_vec = registry_entries.registries();
+        let registries = (0..registry_vec.len())
+            .map(|index| {
+                let registry = registry_vec.get_unchecked(index);
+                (
+                    to_h256(registry.lock_hash().as_slice()),
+                    to_h256(registry.state().as_slice()),
+                )
+            })
+            .collect();
+        Ok(RegistryEntry {
+            registries,
+            proof: registry_entries.proof().raw_data().to_vec(),
+        })
+    }
+
+    /// Check that the proof of the registries with their states belongs to the SMT root
+    pub fn verify(&self, root: &H256) -> Result<bool, Error> {
+        CompiledMerkleProof(self.proof.clone())
+            .verify::<Blake2bHasher>(root, self.registries.clone())
+            .map_err(|e| Error::RegistryEntryError(e.to_string()))
+    }
+
+    /// Check that the proof transitions the old SMT root where the registries are absent to the
+    /// new SMT root where the registries are set, as the registry contract does on chain
+    pub fn verify_transition(
+        &self,
+        old_root: &H256,
+        new_root: &H256,
+    ) -> Result<(bool, bool), Error> {
+        let previous_registries = self
+            .registries
+            .iter()
+            .map(|(lock_hash, _)| (*lock_hash, H256::zero()))
+            .collect();
+        let proof = CompiledMerkleProof(self.proof.clone());
+        let old_root_verified = proof
+            .verify::<Blake2bHasher>(old_root, previous_registries)
+            .map_err(|e| Error::RegistryEntryError(e.to_string()))?;
+        Ok((old_root_verified, self.verify(new_root)?))
+    }
+
+    pub fn to_json(&self) -> Value {
+        let registries = self
+            .registries
+            .iter()
+            .map(|(lock_hash, state)| {
+                json!({
+                    "lock_hash": hex::encode(lock_hash.as_slice()),
+                    "state": hex::encode(state.as_slice()),
+                })
+            })
+            .collect();
+        let mut entry = Map::new();
+        entry.insert("registries".to_string(), Value::Array(registries));
+        entry.insert("proof".to_string(), Value::String(hex::encode(&self.proof)));
+        Value::Object(entry)
+    }
+}
+
+/// Decode the hex registry entry into JSON, and verify its proof when the SMT root is given
+pub fn decode_registry_entry(entry: &str, root: Option<&str>) -> Result<Value, Error> {
+    let registry_entry = RegistryEntry::from_hex(entry)?;
+    let mut entry_json = registry_entry.to_json();
+    if let Some(root) = root {
+        let verified = registry_entry.verify(&parse_root(root)?)?;
+        entry_json["verified"] = Value::Bool(verified);
+    }
+    Ok(entry_json)
+}
+
+pub fn verify_registry_entry(entry: &str, old_root: &str, new_root: &str) -> Result<Value, Error> {
+    let registry_entry = RegistryEntry::from_hex(entry)?;
+    let (old_root_verified, new_root_verified) =
+        registry_entry.verify_transition(&parse_root(old_root)?, &parse_root(new_root)?)?;
+    Ok(json!({
+        "old_root_verified": old_root_verified,
+        "new_root_verified": new_root_verified,
+        "verified": old_root_verified && new_root_verified,
+    }))
+}
+
+pub fn parse_root(root: &str) -> Result<H256, Error> {
+    let root_bytes = decode_hex(root, "SMT root")?;
+    if root_bytes.len() != 32 {
+        return Err(Error::RegistryEntryError(format!(
+            "SMT root '{}' must be 32bytes hex string",
+            root
+        )));
+    }
+    Ok(to_h256(&root_bytes))
+}
+
+fn decode_hex(value: &str, name: &str) -> Result<Vec<u8>, Error> {
+    let hex_str = value.strip_prefix("0x").unwrap_or(value);
+    hex::decode(hex_str)
+        .map_err(|_e| Error::RegistryEntryError(format!("{} '{}' is not hex string", name, value)))
+}
+
+fn to_h256(slice: &[u8]) -> H256 {
+    let bytes: [u8; 32] = slice.try_into().expect("Byte32 must be 32 bytes");
+    H256::from(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::smt::entry::encode_registry_entry;
+    use sparse_merkle_tree::default_store::DefaultStore;
+    use sparse_merkle_tree::SparseMerkleTree;
+
+    fn generate_entry(leaves: Vec<(H256, H256)>) -> (H256, String) {
+        let mut smt = SparseMerkleTree::<Blake2bHasher, H256, DefaultStore<H256>>::default();
+        smt.update_all(leaves.clone()).unwrap();
+        let proof: Vec<u8> = smt
+            .merkle_proof(leaves.iter().map(|leaf| leaf.0).collect())
+            .unwrap()
+            .compile(leaves.clone())
+            .unwrap()
+            .into();
+        (*smt.root(), encode_registry_entry(&leaves, proof))
+    }
+
+    #[test]
+    fn test_decode_registry_entry() {
+        let leaves = vec![
+            (H256::from([1u8; 32]), H256::from([255u8; 32])),
+            (H256::from([2u8; 32]), H256::from([255u8; 32])),
+        ];
+        let (root, entry_hex) = generate_entry(leaves.clone());
+
+        let entry = RegistryEntry::from_hex(&format!("0x{}", entry_hex)).unwrap();
+        assert_eq!(entry.registries, leaves);
+        assert_eq!(entry.verify(&root), Ok(true));
+        assert_eq!(entry.verify(&H256::from([3u8; 32])), Ok(false));
+
+        let root_hex = hex::encode(root.as_slice());
+        let entry_json = decode_registry_entry(&entry_hex, Some(&root_hex)).unwrap();
+        assert_eq!(entry_json["verified"], json!(true));
+        assert_eq!(entry_json["proof"], json!(hex::encode(&entry.proof)));
+        assert_eq!(
+            decode_registry_entry(&entry_hex, None).unwrap()["verified"],
+            json!(null)
+        );
+        assert_eq!(
+            entry.to_json()["registries"][1],
+            json!({
+                "lock_hash": hex::encode([2u8; 32]),
+                "state": hex::encode([255u8; 32]),
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_registry_entry() {
+        let registered = (H256::from([1u8; 32]), H256::from([255u8; 32]));
+        let (old_root, _) = generate_entry(vec![registered]);
+        let update_leaves = vec![(H256::from([2u8; 32]), H256::from([255u8; 32]))];
+        let mut smt = SparseMerkleTree::<Blake2bHasher, H256, DefaultStore<H256>>::default();
+        smt.update_all(vec![registered]).unwrap();
+        smt.update_all(update_leaves.clone()).unwrap();
+        let proof: Vec<u8> = smt
+            .merkle_proof(vec![H256::from([2u8; 32])])
+            .unwrap()
+            .compile(update_leaves.clone())
+            .unwrap()
+            .into();
+        let entry_hex = encode_registry_entry(&update_leaves, proof);
+        let new_root = hex::encode(smt.root().as_slice());
+        let old_root = hex::encode(old_root.as_slice());
+
+        assert_eq!(
+            verify_registry_entry(&entry_hex, &old_root, &new_root),
+            Ok(json!({
+                "old_root_verified": true,
+                "new_root_verified": true,
+                "verified": true,
+            }))
+        );
+        assert_eq!(
+            verify_registry_entry(&entry_hex, &new_root, &old_root),
+            Ok(json!({
+                "old_root_verified": false,
+                "new_root_verified": false,
+                "verified": false,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_decode_registry_entry_error() {
+        assert_eq!(
+            RegistryEntry::from_hex("0xzz"),
+            Err(Error::RegistryEntryError(
+                "Registry entry '0xzz' is not hex string".to_owned()
+            ))
+        );
+        assert_eq!(
+            RegistryEntry::from_hex("0x1234"),
+            Err(Error::RegistryEntryError(
+                "Registry entry is not CotaNFTRegistryEntries".to_owned()
+            ))
+        );
+        assert_eq!(
+            parse_root("0x1234"),
+            Err(Error::RegistryEntryError(
+                "SMT root '0x1234' must be 32bytes hex string".to_owned()
+            ))
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use crate::admin::build_admin_rpc_handler;
+use crate::api::{build_rpc_handler, RpcHandler};
+use crate::api_key::RequestMeta;
+use crate::config::{get_config, set_config, Config};
+use crate::db::get_connection;
+use crate::migration::run_pending_migrations;
+use crate::smt::entry::encode_registry_entry;
+use crate::test_support::mock_indexer::{registry_cell_data, MockIndexer, MockResponse};
+use cota_smt::smt::{Blake2bHasher, H256};
+use diesel::connection::SimpleConnection;
+use jsonrpc_http_server::jsonrpc_core::IoHandler;
+use lazy_static::lazy_static;
+use parking_lot::{Mutex, ReentrantMutex};
+use serde_json::{json, Value};
+use sparse_merkle_tree::default_store::DefaultStore;
+use sparse_merkle_tree::SparseMerkleTree;
+use std::env;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::runtime::Runtime;
+
+type MemorySMT = SparseMerkleTree<Blake2bHasher, H256, DefaultStore<H256>>;
+
+pub const SYNCER_TIP_BLOCK_NUMBER: u64 = 100;
+/// The lock hash registered in the database before the tests
+pub const REGISTERED_LOCK_HASH: [u8; 32] = [1u8; 32];
+
+lazy_static! {
+    static ref HARNESS: Harness = Harness::new();
+}
+
+/// The JSON-RPC handler booted against a temp SQLite database, a temp RocksDB and a mock CKB
+/// indexer whose registry cell holds the SMT root of the registered lock hashes in the database.
+///
+/// It lives as long as the test process because the handler depends on the global database
+/// pool, RocksDB and indexer client.
+pub struct Harness {
+    io:           RpcHandler,
+    admin_io:     IoHandler,
+    runtime:      Runtime,
+    lock:         ReentrantMutex<()>,
+    indexer:      Mutex<MockIndexer>,
+    onchain_root: H256,
+    _temp_dir:    TempDir,
+}
+
+impl Harness {
+    pub fn get() -> &'static Harness {
+        &HARNESS
+    }
+
+    fn new() -> Self {
+        let temp_dir = tempfile::tempdir().expect("Temp dir create error");
+        let (onchain_root, _) = generate_registry_entry(vec![registered_leaf()], vec![]);
+        let indexer = MockIndexer::start(MockResponse::Cells(vec![registry_cell_data(
+            onchain_root.into(),
+        )]));
+
+        env::set_var("DATABASE_URL", temp_dir.path().join("registry.sqlite3"));
+        env::set_var("ROCKSDB_PATH", temp_dir.path().join("store.db"));
+        env::set_var("CKB_INDEXER", indexer.url());
+
+        set_config(Config {
+            registry_cell_ttl: Duration::from_secs(0),
+            ..get_config()
+        });
+
+        run_pending_migrations().expect("Test database migration error");
+        get_connection()
+            .expect("Test database connection error")
+            .batch_execute(&format!(
+                "INSERT INTO check_infos (check_type, block_number, block_hash, created_at, \
+                 updated_at) VALUES (0, {}, '{}', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP);
+                 INSERT INTO register_cota_kv_pairs (block_number, lock_hash, created_at, \
+                 updated_at) VALUES ({}, '{}', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP);",
+                SYNCER_TIP_BLOCK_NUMBER,
+                hex::encode([0u8; 32]),
+                SYNCER_TIP_BLOCK_NUMBER,
+                hex::encode(REGISTERED_LOCK_HASH),
+            ))
+            .expect("Test database seed error");
+
+        Harness {
+            io: build_rpc_handler(),
+            admin_io: build_admin_rpc_handler(),
+            runtime: Runtime::new().expect("Test runtime build error"),
+            lock: ReentrantMutex::new(()),
+            indexer: Mutex::new(indexer),
+            onchain_root,
+            _temp_dir: temp_dir,
+        }
+    }
+
+    /// Run the requests of `f` one by one against the registry cell holding the SMT root, and
+    /// restore the registry cell of the registered lock hashes in the database afterwards.
+    pub fn with_onchain_root<T>(&self, root: H256, f: impl FnOnce(&Self) -> T) -> T {
+        let _guard = self.lock.lock();
+        self.set_onchain_root(root);
+        let result = f(self);
+        self.set_onchain_root(self.onchain_root);
+        result
+    }
+
+    fn set_onchain_root(&self, root: H256) {
+        self.indexer
+            .lock()
+            .set_response(MockResponse::Cells(vec![registry_cell_data(root.into())]));
+    }
+
+    /// Send the JSON-RPC request and return the full JSON response, the requests are handled one
+    /// by one so that every registration starts from the SMT of the registry cell.
+    pub fn request(&self, method: &str, params: Value) -> Value {
+        let _guard = self.lock.lock();
+        let response = self
+            .runtime
+            .block_on(
+                self.io
+                    .handle_request(&request_body(method, params), RequestMeta::default()),
+            )
+            .expect("JSON-RPC response should exist");
+        serde_json::from_str(&response).expect("JSON-RPC response should be JSON")
+    }
+
+    /// Send the JSON-RPC request to the admin handler like `request`
+    pub fn admin_request(&self, method: &str, params: Value) -> Value {
+        let _guard = self.lock.lock();
+        let response = self
+            .runtime
+            .block_on(self.admin_io.handle_request(&request_body(method, params)))
+            .expect("JSON-RPC response should exist");
+        serde_json::from_str(&response).expect("JSON-RPC response should be JSON")
+    }
+}
+
+fn request_body(method: &str, params: Value) -> String {
+    json!({
+        "id": 1,
+        "jsonrpc": "2.0",
+        "method": method,
+        "params": params,
+    })
+    .to_string()
+}
+
+pub fn registered_leaf() -> (H256, H256) {
+    (H256::from(REGISTERED_LOCK_HASH), H256::from([255u8; 32]))
+}
+
+/// Generate the SMT root and the registry entry of updating the leaves on the SMT of the base
+/// leaves in memory.
+pub fn generate_registry_entry(
+    base_leaves: Vec<(H256, H256)>,
+    update_leaves: Vec<(H256, H256)>,
+) -> (H256, String) {
+    let mut smt = MemorySMT::default();
+    smt.update_all(base_leaves).expect("SMT update error");
+    if update_leaves.is_empty() {
+        return (*smt.root(), String::new());
+    }
+    smt.update_all(update_leaves.clone())
+        .expect("SMT update error");
+    let merkle_proof = smt
+        .merkle_proof(update_leaves.iter().map(|leaf| leaf.0).collect())
+        .expect("SMT merkle proof error");
+    let merkle_proof_vec: Vec<u8> = merkle_proof
+        .compile(update_leaves.clone())
+        .expect("SMT merkle proof compile error")
+        .into();
+    (
+        *smt.root(),
+        encode_registry_entry(&update_leaves, merkle_proof_vec),
+    )
+}
@@ -0,0 +1,143 @@
+use jsonrpc_http_server::jsonrpc_core::{Error, ErrorCode, IoHandler, Value};
+use jsonrpc_http_server::{Server, ServerBuilder};
+use parking_lot::Mutex;
+use serde_json::json;
+use std::sync::Arc;
+
+pub const REGISTRY_TX_HASH: &str =
+    "0x1d3b2c14e9c2b0fc08d5b7db4ab7a0c4fdfa66da0b1e46df0f9d0daa5f3a2b6c";
+pub const REGISTRY_BLOCK_NUMBER: u64 = 4735280;
+
+#[derive(Clone)]
+pub enum MockResponse {
+    /// Answer with the registry cells holding the data
+    Cells(Vec<Vec<u8>>),
+    /// Answer with a JSON-RPC error
+    Failure(String),
+    /// Answer with a result which is not a cell pagination
+    Malformed,
+}
+
+struct MockState {
+    cells:          MockResponse,
+    // The data of the live cell, the first cell data of `cells` is used if it is none
+    live_cell_data: Option<Vec<u8>>,
+    live_status:    String,
+    requests:       usize,
+}
+
+/// A local JSON-RPC server answering `get_cells` and `get_live_cell` like the CKB indexer and
+/// node with the configured registry cells.
+pub struct MockIndexer {
+    server: Server,
+    state:  Arc<Mutex<MockState>>,
+}
+
+impl MockIndexer {
+    pub fn start(response: MockResponse) -> Self {
+        let state = Arc::new(Mutex::new(MockState {
+            cells:          response,
+            live_cell_data: None,
+            live_status:    "live".to_owned(),
+            requests:       0,
+        }));
+
+        let mut io = IoHandler::default();
+        let cells_state = Arc::clone(&state);
+        io.add_sync_method("get_cells", move |_params| {
+            let mut state = cells_state.lock();
+            state.requests += 1;
+            match &state.cells {
+                MockResponse::Cells(cells) => {
+                    let objects: Vec<Value> = cells
+                        .iter()
+                        .enumerate()
+                        .map(|(index, data)| generate_cell(index, data))
+                        .collect();
+                    Ok(json!({ "objects": objects, "last_cursor": "0x" }))
+                }
+                MockResponse::Failure(message) => Err(Error {
+                    code:    ErrorCode::InternalError,
+                    message: message.clone(),
+                    data:    None,
+                }),
+                MockResponse::Malformed => Ok(json!({ "cells": "0x" })),
+            }
+        });
+        let live_cell_state = Arc::clone(&state);
+        io.add_sync_method("get_live_cell", move |_params| {
+            let mut state = live_cell_state.lock();
+            state.requests += 1;
+            let data = match (&state.live_cell_data, &state.cells) {
+                (Some(data), _) => data.clone(),
+                (None, MockResponse::Cells(cells)) if !cells.is_empty() => cells[0].clone(),
+                _ => vec![],
+            };
+            Ok(json!({
+                "cell": {
+                    "data": {
+                        "content": format!("0x{}", hex::encode(&data)),
+                        "hash": format!("0x{}", hex::encode([0u8; 32])),
+                    },
+                    "output": cell_output(),
+                },
+                "status": state.live_status,
+            }))
+        });
+
+        let server = ServerBuilder::new(io)
+            .start_http(&"127.0.0.1:0".parse().unwrap())
+            .expect("Mock indexer start error");
+        MockIndexer { server, state }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.server.address())
+    }
+
+    pub fn set_response(&self, response: MockResponse) {
+        self.state.lock().cells = response;
+    }
+
+    pub fn set_live_cell(&self, data: Option<Vec<u8>>, status: &str) {
+        let mut state = self.state.lock();
+        state.live_cell_data = data;
+        state.live_status = status.to_owned();
+    }
+
+    pub fn requests(&self) -> usize {
+        self.state.lock().requests
+    }
+}
+
+/// Generate the registry cell data of version 0 with the SMT root
+pub fn registry_cell_data(smt_root: [u8; 32]) -> Vec<u8> {
+    let mut data = vec![0u8];
+    data.extend_from_slice(&smt_root);
+    data
+}
+
+fn generate_cell(index: usize, data: &[u8]) -> Value {
+    json!({
+        "output": cell_output(),
+        "output_data": format!("0x{}", hex::encode(data)),
+        "out_point": {
+            "tx_hash": REGISTRY_TX_HASH,
+            "index": format!("{:#x}", index),
+        },
+        "block_number": format!("{:#x}", REGISTRY_BLOCK_NUMBER),
+        "tx_index": "0x0",
+    })
+}
+
+fn cell_output() -> Value {
+    json!({
+        "capacity": "0x0",
+        "lock": {
+            "code_hash": format!("0x{}", hex::encode([0u8; 32])),
+            "hash_type": "type",
+            "args": "0x",
+        },
+        "type": null,
+    })
+}
@@ -0,0 +1,17 @@
+//! The local stand-ins of the external services used by the tests.
+
+#[cfg(feature = "sqlite")]
+pub mod harness;
+pub mod mock_indexer;
+
+use std::future::Future;
+
+/// Run the future on a new runtime, the mock servers must be dropped outside of it because they
+/// own runtimes which can't be dropped in an async context.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Test runtime build error")
+        .block_on(future)
+}
@@ -1,13 +1,113 @@
+use crate::address::{calc_lock_hash, parse_address};
+use crate::config::get_config;
 use crate::error::Error;
+use ckb_jsonrpc_types::Script;
 use hex;
 use jsonrpc_http_server::jsonrpc_core::Value;
+use std::collections::HashMap;
 use std::convert::TryInto;
+use std::env;
+use std::str::FromStr;
 
 fn remove_0x(str: &str) -> &str {
-    if str.contains("0x") {
-        &str[2..]
-    } else {
-        str
+    str.strip_prefix("0x").unwrap_or(str)
+}
+
+/// Every lock param is a lock hash hex string, a lock script object or a CKB address, and the
+/// lock hashes of the lock scripts and the addresses are computed here.
+///
+/// The params must be non-empty, no more than `MAX_BATCH_SIZE` and free of duplicate locks, and
+/// all the invalid params are reported with their indexes rather than only the first one.
+pub fn parse_lock_hash_param(params: Vec<Value>) -> Result<Vec<[u8; 32]>, Error> {
+    parse_lock_params(params, parse_lock_hash, |lock_hash| *lock_hash)
+}
+
+/// Every registry param is either a lock param or an object of `lock_hash` and the optional
+/// `cota_cell_id`, the ccid is `u64::MAX` if it is not given.
+pub fn parse_registry_param(params: Vec<Value>) -> Result<Vec<([u8; 32], u64)>, Error> {
+    parse_lock_params(params, parse_registry, |registry| registry.0)
+}
+
+fn parse_lock_params<T>(
+    params: Vec<Value>,
+    parse: fn(Value) -> Result<T, Error>,
+    lock_hash_of: fn(&T) -> [u8; 32],
+) -> Result<Vec<T>, Error> {
+    if params.is_empty() {
+        return Err(Error::RequestParamsInvalid(
+            "Request params must not be empty".to_owned(),
+        ));
+    }
+    let max_batch_size = get_config().max_batch_size;
+    if params.len() > max_batch_size {
+        return Err(Error::RequestParamsInvalid(format!(
+            "Request params must not be more than {}",
+            max_batch_size
+        )));
+    }
+    let mut results: Vec<T> = Vec::with_capacity(params.len());
+    let mut invalid_params: Vec<(usize, String)> = vec![];
+    let mut first_indexes: HashMap<[u8; 32], usize> = HashMap::new();
+    for (index, param) in params.into_iter().enumerate() {
+        match parse(param) {
+            Ok(result) => {
+                let lock_hash = lock_hash_of(&result);
+                if let Some(first_index) = first_indexes.get(&lock_hash) {
+                    invalid_params.push((
+                        index,
+                        format!("Request param is duplicate of the param {}", first_index),
+                    ));
+                } else {
+                    first_indexes.insert(lock_hash, index);
+                }
+                results.push(result);
+            }
+            Err(err) => invalid_params.push((index, err.to_msg())),
+        }
+    }
+    if !invalid_params.is_empty() {
+        return Err(Error::RequestParamElementsInvalid(invalid_params));
+    }
+    Ok(results)
+}
+
+fn parse_registry(param: Value) -> Result<([u8; 32], u64), Error> {
+    let registry = match param.as_object() {
+        Some(registry) if registry.contains_key("lock_hash") => registry,
+        _ => return parse_lock_hash(param).map(|lock_hash| (lock_hash, u64::MAX)),
+    };
+    let registry_error = || Error::RequestParamRegistryInvalid(param.to_string());
+    if registry
+        .keys()
+        .any(|key| key != "lock_hash" && key != "cota_cell_id")
+    {
+        return Err(registry_error());
+    }
+    // The lock_hash field accepts the lock hash, the address and the lock script like the array
+    let lock_hash = parse_lock_hash(registry["lock_hash"].clone())?;
+    let ccid = match registry.get("cota_cell_id") {
+        None | Some(Value::Null) => u64::MAX,
+        Some(ccid) => ccid.as_u64().ok_or_else(registry_error)?,
+    };
+    Ok((lock_hash, ccid))
+}
+
+fn parse_lock_hash(param: Value) -> Result<[u8; 32], Error> {
+    match &param {
+        Value::String(value) if value.starts_with("0x") => {
+            Ok(parse_request_param::<32>(vec![param.clone()])?[0])
+        }
+        // A lock hash without 0x is never an address, so it is reported as a lock hash
+        Value::String(value)
+            if value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            Err(Error::RequestParamHexInvalid(param.to_string()))
+        }
+        Value::String(address) => parse_address(address).map(|lock| calc_lock_hash(&lock)),
+        Value::Object(_) => serde_json::from_value::<Script>(param.clone())
+            .map(|lock| calc_lock_hash(&lock.into()))
+            .map_err(|_| Error::RequestParamLockScriptInvalid(param.to_string())),
+        _ => Err(Error::RequestParamHexInvalid(param.to_string())),
     }
 }
 
@@ -18,7 +118,7 @@ pub fn parse_request_param<const N: usize>(params: Vec<Value>) -> Result<Vec<[u8
             return Err(Error::RequestParamHexInvalid(param.to_string()));
         }
         let hex_str = param.as_str().unwrap();
-        if !hex_str.contains("0x") {
+        if !hex_str.starts_with("0x") {
             return Err(Error::RequestParamHexInvalid(param.to_string()));
         }
         let hex_without_0x = remove_0x(hex_str);
@@ -48,9 +148,31 @@ pub fn parse_bytes_n<const N: usize>(value: String) -> Result<[u8; N], Error> {
     Ok(parse_n(vec))
 }
 
+pub fn parse_env<T: FromStr>(key: &str, default: T) -> T {
+    match env::var(key) {
+        Ok(value) => value
+            .parse::<T>()
+            .unwrap_or_else(|_| panic!("{} is invalid", key)),
+        Err(_e) => default,
+    }
+}
+
+pub fn parse_env_opt<T: FromStr>(key: &str) -> Option<T> {
+    env::var(key).ok().map(|value| {
+        value
+            .parse::<T>()
+            .unwrap_or_else(|_| panic!("{} is invalid", key))
+    })
+}
+
+pub fn is_mainnet() -> bool {
+    parse_env("IS_MAINNET", false)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::json;
 
     #[test]
     fn test_remove_0x() {
@@ -58,6 +180,133 @@ mod tests {
         assert_eq!(remove_0x("123456"), "123456");
         assert_eq!(remove_0x("0"), "0");
         assert_eq!(remove_0x("0x"), "");
+        assert_eq!(remove_0x("120x34"), "120x34");
+    }
+
+    #[test]
+    fn test_parse_lock_hash_param() {
+        let lock = json!({
+            "code_hash": "0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8",
+            "hash_type": "type",
+            "args": "0xb39bbc0b3673c7d36450bc14cfcdad2d559c6c64",
+        });
+        let lock_script: Script = serde_json::from_value(lock.clone()).unwrap();
+        let lock_hash = calc_lock_hash(&lock_script.into());
+        assert_eq!(
+            parse_lock_hash_param(vec![json!(format!("0x{}", hex::encode(lock_hash))), lock]),
+            Ok(vec![lock_hash, lock_hash])
+        );
+
+        let invalid_lock = json!({ "code_hash": "0x1234", "hash_type": "type", "args": "0x" });
+        assert_eq!(
+            parse_lock_hash_param(vec![invalid_lock.clone()]),
+            Err(Error::RequestParamElementsInvalid(vec![(
+                0,
+                Error::RequestParamLockScriptInvalid(invalid_lock.to_string()).to_msg()
+            )]))
+        );
+    }
+
+    #[test]
+    fn test_parse_lock_hash_param_validation() {
+        let lock_hash = format!("0x{}", hex::encode([1u8; 32]));
+        let upper_lock_hash = format!("0x{}", hex::encode_upper([1u8; 32]));
+        let without_0x = hex::encode([2u8; 32]);
+        let inner_0x = format!("ab0x{}", hex::encode([2u8; 31]));
+        assert_eq!(
+            parse_lock_hash_param(vec![json!(upper_lock_hash)]),
+            Ok(vec![[1u8; 32]])
+        );
+        assert_eq!(
+            parse_lock_hash_param(vec![
+                json!(lock_hash),
+                json!(without_0x),
+                json!(upper_lock_hash),
+                json!(inner_0x),
+                json!(1),
+            ]),
+            Err(Error::RequestParamElementsInvalid(vec![
+                (
+                    1,
+                    Error::RequestParamHexInvalid(json!(without_0x).to_string()).to_msg()
+                ),
+                (2, "Request param is duplicate of the param 0".to_owned()),
+                (3, parse_lock_hash(json!(inner_0x)).unwrap_err().to_msg()),
+                (4, Error::RequestParamHexInvalid("1".to_owned()).to_msg()),
+            ]))
+        );
+
+        assert_eq!(
+            parse_lock_hash_param(vec![]),
+            Err(Error::RequestParamsInvalid(
+                "Request params must not be empty".to_owned()
+            ))
+        );
+        assert_eq!(
+            parse_lock_hash_param(vec![json!(lock_hash); 101]),
+            Err(Error::RequestParamsInvalid(
+                "Request params must not be more than 100".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_registry_param() {
+        let lock_hash = format!("0x{}", hex::encode([1u8; 32]));
+        assert_eq!(
+            parse_registry_param(vec![
+                json!({ "lock_hash": lock_hash, "cota_cell_id": 8 }),
+                json!({ "lock_hash": format!("0x{}", hex::encode([2u8; 32])) }),
+                json!(format!("0x{}", hex::encode([3u8; 32]))),
+            ]),
+            Ok(vec![
+                ([1u8; 32], 8),
+                ([2u8; 32], u64::MAX),
+                ([3u8; 32], u64::MAX)
+            ])
+        );
+
+        let lock = json!({
+            "code_hash": "0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8",
+            "hash_type": "type",
+            "args": "0xb39bbc0b3673c7d36450bc14cfcdad2d559c6c64",
+        });
+        let lock_script: Script = serde_json::from_value(lock.clone()).unwrap();
+        assert_eq!(
+            parse_registry_param(vec![json!({ "lock_hash": lock, "cota_cell_id": 8 })]),
+            Ok(vec![(calc_lock_hash(&lock_script.into()), 8)])
+        );
+
+        let invalid_lock_hash = json!({ "lock_hash": "0x1234", "cota_cell_id": 8 });
+        assert_eq!(
+            parse_registry_param(vec![invalid_lock_hash]),
+            Err(Error::RequestParamElementsInvalid(vec![(
+                0,
+                Error::RequestParamHexInvalid("\"0x1234\"".to_owned()).to_msg()
+            )]))
+        );
+
+        let invalid_ccid = json!({ "lock_hash": lock_hash, "cota_cell_id": "8" });
+        let unknown_field = json!({ "lock_hash": lock_hash, "ccid": 8 });
+        assert_eq!(
+            parse_registry_param(vec![
+                json!({ "lock_hash": lock_hash, "cota_cell_id": 8 }),
+                invalid_ccid.clone(),
+                unknown_field.clone(),
+                json!(lock_hash),
+            ]),
+            Err(Error::RequestParamElementsInvalid(vec![
+                (
+                    1,
+                    Error::RequestParamRegistryInvalid(invalid_ccid.to_string()).to_msg()
+                ),
+                (
+                    2,
+                    Error::RequestParamRegistryInvalid(unknown_field.to_string()).to_msg()
+                ),
+                (3, "Request param is duplicate of the param 0".to_owned()),
+            ]))
+        );
     }
 
     #[test]